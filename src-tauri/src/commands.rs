@@ -1,29 +1,123 @@
-use crate::connection::{ConnectionStore, SavedConnection};
+use crate::connection::{ConnectionStore, SavedConnection, SshConfig};
 use crate::db::{
-    AlterTableParams, ColumnInfo, ConnectionFactory, DatabaseType, DbConnection, FetchDataParams,
-    FilterCondition, IndexInfo, QueryResult, RowDelete, RowInsert, RowUpdate, SchemaInfo,
-    SortColumn, TableData, TableInfo,
+    accepts_returning, allowed_read_only, classify, find_destructive, resolve_params,
+    split_statements, AlterTableParams, ColumnChangeAction, ColumnInfo, ConnectionFactory,
+    ConnectionParams, DatabaseType, DbConnection, DbError, Destructive, DestructiveKind,
+    FetchDataParams, FilterNode, IndexInfo, QueryEvent, QueryLimits, QueryMessage, QueryParams,
+    QueryPlan, QueryResult, ResultColumn, RowDelete, RowInsert, RowUpdate, SchemaInfo, SortColumn,
+    StatementKind, StatementResult, TableData, TableInfo, TableRef, TlsConfig,
 };
+use crate::guard::{ConfirmationGuard, ConfirmationRequired};
+use crate::history::{HistoryEntry, HistoryFilter, HistoryStore, PruneOptions};
+use crate::import::{ImportSource, SkippedEntry};
+use crate::secrets::{SecretKind, VaultFiles};
+use crate::snippets::{Snippet, SnippetStore};
+use crate::tunnel::{HostKeyConfirmation, SshTunnel, TunnelEndpoint};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
-use tauri::State;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, State};
 use tokio::sync::RwLock;
 
 pub struct AppState {
     pub connection_store: RwLock<ConnectionStore>,
     pub active_connections: RwLock<HashMap<String, Arc<dyn DbConnection>>>,
+    pub tunnels: RwLock<HashMap<String, SshTunnel>>,
+    pub history: HistoryStore,
+    pub snippet_store: RwLock<SnippetStore>,
+    pub confirmations: ConfirmationGuard,
+    pub vault_files: VaultFiles,
 }
 
-impl Default for AppState {
-    fn default() -> Self {
+impl AppState {
+    pub fn new(vault_files: VaultFiles, history: HistoryStore) -> Self {
         Self {
             connection_store: RwLock::new(ConnectionStore::load()),
             active_connections: RwLock::new(HashMap::new()),
+            tunnels: RwLock::new(HashMap::new()),
+            history,
+            snippet_store: RwLock::new(SnippetStore::load()),
+            confirmations: ConfirmationGuard::default(),
+            vault_files,
         }
     }
 }
 
+/// Error type of commands that can stop to ask for confirmation. Ordinary
+/// failures still reach the frontend as a plain message string.
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum CommandError {
+    Message(String),
+    ConfirmationRequired(ConfirmationRequired),
+    HostKeyConfirmation(HostKeyConfirmation),
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Message(message)
+    }
+}
+
+impl From<&str> for CommandError {
+    fn from(message: &str) -> Self {
+        CommandError::Message(message.to_string())
+    }
+}
+
+/// The default limits stored with the connection.
+async fn saved_limits(state: &AppState, connection_id: &str) -> QueryLimits {
+    let store = state.connection_store.read().await;
+    let saved = store.connections.iter().find(|c| c.id == connection_id);
+    QueryLimits {
+        timeout_ms: saved.and_then(|c| c.statement_timeout_ms),
+        max_rows: saved.and_then(|c| c.max_rows),
+    }
+}
+
+async fn is_production(state: &AppState, connection_id: &str) -> bool {
+    let store = state.connection_store.read().await;
+    store
+        .connections
+        .iter()
+        .any(|c| c.id == connection_id && c.production)
+}
+
+/// Lets a destructive operation on a production connection through only with
+/// a token issued for exactly this statement. Otherwise describes what would
+/// run, with a row estimate, and issues a token for the retry.
+async fn require_confirmation(
+    state: &AppState,
+    conn: &dyn DbConnection,
+    connection_id: &str,
+    destructive: Destructive,
+    statement: &str,
+    token: Option<&str>,
+) -> Result<(), CommandError> {
+    if token.is_some_and(|token| state.confirmations.confirm(token, connection_id, statement)) {
+        return Ok(());
+    }
+
+    let mut estimated_rows = None;
+    for table in &destructive.tables {
+        if let Ok(Some(rows)) = conn
+            .estimate_row_count(table.schema.as_deref(), &table.name)
+            .await
+        {
+            estimated_rows = Some(estimated_rows.unwrap_or(0) + rows);
+        }
+    }
+
+    Err(CommandError::ConfirmationRequired(ConfirmationRequired {
+        kind: destructive.kind,
+        statement: statement.to_string(),
+        tables: destructive.tables,
+        estimated_rows,
+        confirmation_token: state.confirmations.issue(connection_id, statement),
+    }))
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ConnectionInput {
     pub name: String,
@@ -33,6 +127,46 @@ pub struct ConnectionInput {
     pub database: String,
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub ssh: Option<SshConfig>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    #[serde(default)]
+    pub read_only: bool,
+    #[serde(default)]
+    pub statement_timeout_ms: Option<u64>,
+    #[serde(default)]
+    pub max_rows: Option<u64>,
+    #[serde(default)]
+    pub production: bool,
+    /// Stored secrets to delete on update; an empty password otherwise keeps
+    /// the stored one.
+    #[serde(default)]
+    pub clear_secrets: Vec<SecretKind>,
+}
+
+impl ConnectionInput {
+    fn into_saved(self) -> SavedConnection {
+        let mut conn = SavedConnection::new(
+            self.name,
+            self.db_type,
+            self.host,
+            self.port,
+            self.database,
+            self.username,
+            self.password,
+        );
+        conn.ssh = self.ssh;
+        conn.tls = self.tls;
+        conn.parameters = self.parameters;
+        conn.read_only = self.read_only;
+        conn.statement_timeout_ms = self.statement_timeout_ms;
+        conn.max_rows = self.max_rows;
+        conn.production = self.production;
+        conn
+    }
 }
 
 #[derive(Debug, Serialize)]
@@ -44,6 +178,44 @@ pub struct ConnectionListItem {
     pub port: u16,
     pub database: String,
     pub username: String,
+    pub ssh: Option<SshConfig>,
+    pub tls: TlsConfig,
+    pub parameters: HashMap<String, String>,
+    pub read_only: bool,
+    pub statement_timeout_ms: Option<u64>,
+    pub max_rows: Option<u64>,
+    pub production: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct VaultStatus {
+    /// False until the first unlock sets the master password.
+    pub initialized: bool,
+    pub unlocked: bool,
+}
+
+#[tauri::command]
+pub async fn get_vault_status(state: State<'_, AppState>) -> Result<VaultStatus, String> {
+    Ok(VaultStatus {
+        initialized: state.vault_files.exists(),
+        unlocked: state.connection_store.read().await.is_unlocked(),
+    })
+}
+
+/// Opens the vault of saved passwords. The first call sets the master
+/// password.
+#[tauri::command]
+pub async fn unlock_vault(
+    state: State<'_, AppState>,
+    master_password: String,
+) -> Result<(), String> {
+    let mut store = state.connection_store.write().await;
+    if store.is_unlocked() {
+        return Ok(());
+    }
+    store
+        .unlock(&state.vault_files, &master_password)
+        .map_err(|e| e.to_string())
 }
 
 #[tauri::command]
@@ -62,6 +234,13 @@ pub async fn get_connections(
             port: c.port,
             database: c.database.clone(),
             username: c.username.clone(),
+            ssh: c.ssh.clone(),
+            tls: c.tls.clone(),
+            parameters: c.parameters.clone(),
+            read_only: c.read_only,
+            statement_timeout_ms: c.statement_timeout_ms,
+            max_rows: c.max_rows,
+            production: c.production,
         })
         .collect())
 }
@@ -71,31 +250,123 @@ pub async fn save_connection(
     state: State<'_, AppState>,
     input: ConnectionInput,
 ) -> Result<String, String> {
-    let conn = SavedConnection::new(
-        input.name,
-        input.db_type,
-        input.host,
-        input.port,
-        input.database,
-        input.username,
-        input.password,
-    );
+    let conn = input.into_saved();
     let id = conn.id.clone();
 
     let mut store = state.connection_store.write().await;
-    store.add(conn);
+    store.add(conn).map_err(|e| e.to_string())?;
     store.save().map_err(|e| e.to_string())?;
 
     Ok(id)
 }
 
+#[tauri::command]
+pub async fn update_connection(
+    state: State<'_, AppState>,
+    id: String,
+    input: ConnectionInput,
+) -> Result<(), CommandError> {
+    let cleared = input.clear_secrets.clone();
+    let mut conn = input.into_saved();
+    conn.id = id.clone();
+
+    let active = state.active_connections.read().await.get(&id).cloned();
+    let mut store = state.connection_store.write().await;
+    let reconnect = active.is_some()
+        && (!cleared.is_empty()
+            || store
+                .get(&id)
+                .is_some_and(|previous| previous.needs_reconnect(&conn)));
+    // Reconnecting would silently drop the open transaction.
+    if let Some(active) = active.filter(|_| reconnect) {
+        if active.in_transaction().await {
+            return Err(
+                "Commit or roll back the open transaction before changing the connection settings"
+                    .into(),
+            );
+        }
+    }
+    store.update(conn, &cleared).map_err(|e| e.to_string())?;
+    store.save().map_err(|e| e.to_string())?;
+    drop(store);
+
+    if reconnect {
+        open_connection(&state, &id).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn duplicate_connection(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<String, String> {
+    let mut store = state.connection_store.write().await;
+    let new_id = store.duplicate(&id).map_err(|e| e.to_string())?;
+    store.save().map_err(|e| e.to_string())?;
+    Ok(new_id)
+}
+
 #[tauri::command]
 pub async fn delete_connection(state: State<'_, AppState>, id: String) -> Result<(), String> {
     let mut store = state.connection_store.write().await;
-    store.remove(&id);
+    store.remove(&id).map_err(|e| e.to_string())?;
     store.save().map_err(|e| e.to_string())
 }
 
+#[derive(Debug, Serialize)]
+pub struct ImportedConnection {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ImportReport {
+    pub imported: Vec<ImportedConnection>,
+    pub skipped: Vec<SkippedEntry>,
+}
+
+#[tauri::command]
+pub async fn import_connections(
+    state: State<'_, AppState>,
+    source: ImportSource,
+) -> Result<ImportReport, String> {
+    let candidates = source.parse().map_err(|e| e.to_string())?;
+    let mut report = ImportReport {
+        imported: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    let mut store = state.connection_store.write().await;
+    for candidate in candidates {
+        let conn = match candidate {
+            Ok(conn) => conn,
+            Err(skipped) => {
+                report.skipped.push(skipped);
+                continue;
+            }
+        };
+        if let Some(existing) = store.find_matching(&conn) {
+            report.skipped.push(SkippedEntry {
+                entry: conn.name,
+                reason: format!("Already saved as '{}'", existing.name),
+            });
+            continue;
+        }
+
+        report.imported.push(ImportedConnection {
+            id: conn.id.clone(),
+            name: conn.name.clone(),
+        });
+        store.add(conn).map_err(|e| e.to_string())?;
+    }
+    if !report.imported.is_empty() {
+        store.save().map_err(|e| e.to_string())?;
+    }
+
+    Ok(report)
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TestConnectionInput {
     pub db_type: DatabaseType,
@@ -104,46 +375,156 @@ pub struct TestConnectionInput {
     pub database: String,
     pub username: String,
     pub password: String,
+    #[serde(default)]
+    pub ssh: Option<SshConfig>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    #[serde(default)]
+    pub read_only: bool,
 }
 
-#[tauri::command]
-pub async fn test_connection(input: TestConnectionInput) -> Result<bool, String> {
-    let conn_str = ConnectionFactory::build_connection_string(
-        input.db_type,
-        &input.host,
-        input.port,
-        &input.database,
-        &input.username,
-        &input.password,
-    );
+/// Opens the SSH tunnel, if any, and points `params` at its local end.
+async fn open_tunnel(
+    db_type: DatabaseType,
+    ssh: Option<&SshConfig>,
+    params: &mut ConnectionParams,
+) -> Result<Option<SshTunnel>, CommandError> {
+    let Some(ssh) = ssh.filter(|_| db_type != DatabaseType::SQLite) else {
+        return Ok(None);
+    };
 
-    let conn = ConnectionFactory::create(input.db_type, &conn_str)
+    let tunnel = SshTunnel::open(ssh, &params.host, params.port)
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| match e {
+            DbError::UnknownHostKey {
+                host,
+                port,
+                fingerprint,
+            } => CommandError::HostKeyConfirmation(HostKeyConfirmation {
+                host,
+                port,
+                fingerprint,
+            }),
+            e => CommandError::from(e.to_string()),
+        })?;
+    match *tunnel.endpoint() {
+        #[cfg(unix)]
+        TunnelEndpoint::Socket(ref path) => params.socket = Some(path.clone()),
+        #[cfg(not(unix))]
+        TunnelEndpoint::Tcp(port) => {
+            if params.tls.ssl_mode == crate::db::SslMode::VerifyFull {
+                tunnel.close().await;
+                return Err(
+                    "verify-full cannot check the server host name through this SSH tunnel".into(),
+                );
+            }
+            params.host = "127.0.0.1".to_string();
+            params.port = port;
+        }
+    }
+    Ok(Some(tunnel))
+}
 
-    conn.test_connection().await.map_err(|e| e.to_string())?;
-    conn.close().await.map_err(|e| e.to_string())?;
+#[tauri::command]
+pub async fn test_connection(input: TestConnectionInput) -> Result<bool, CommandError> {
+    let mut params = ConnectionParams {
+        host: input.host,
+        port: input.port,
+        database: input.database,
+        username: input.username,
+        password: input.password,
+        tls: input.tls,
+        parameters: input.parameters,
+        read_only: input.read_only,
+        socket: None,
+    };
+    let tunnel = open_tunnel(input.db_type, input.ssh.as_ref(), &mut params).await?;
 
-    Ok(true)
+    let result = async {
+        let conn = ConnectionFactory::create(input.db_type, &params)
+            .await
+            .map_err(|e| e.to_string())?;
+        conn.test_connection().await.map_err(|e| e.to_string())?;
+        conn.close().await.map_err(|e| e.to_string())
+    }
+    .await;
+
+    if let Some(tunnel) = tunnel {
+        tunnel.close().await;
+    }
+    result.map(|_| true).map_err(CommandError::from)
 }
 
-#[tauri::command]
-pub async fn connect_to_database(state: State<'_, AppState>, id: String) -> Result<(), String> {
+async fn open_connection(state: &AppState, id: &str) -> Result<(), CommandError> {
     let store = state.connection_store.read().await;
-    let saved = store.get(&id).ok_or("Connection not found")?.clone();
+    let mut saved = store.get(id).ok_or("Connection not found")?.clone();
+    store
+        .resolve_secrets(&mut saved)
+        .map_err(|e| e.to_string())?;
     drop(store);
 
-    let conn_str = saved.connection_string();
-    let db_conn = ConnectionFactory::create(saved.db_type, &conn_str)
-        .await
-        .map_err(|e| e.to_string())?;
+    let mut params = saved.connection_params();
+    let tunnel = open_tunnel(saved.db_type, saved.ssh.as_ref(), &mut params).await?;
+
+    let db_conn = match ConnectionFactory::create(saved.db_type, &params).await {
+        Ok(conn) => conn,
+        Err(e) => {
+            if let Some(tunnel) = tunnel {
+                tunnel.close().await;
+            }
+            return Err(e.to_string().into());
+        }
+    };
 
     let mut active = state.active_connections.write().await;
-    active.insert(id, db_conn);
+    if let Some(previous) = active.insert(id.to_string(), db_conn) {
+        let _ = previous.close().await;
+    }
+    drop(active);
+
+    let mut tunnels = state.tunnels.write().await;
+    let previous_tunnel = match tunnel {
+        Some(tunnel) => tunnels.insert(id.to_string(), tunnel),
+        None => tunnels.remove(id),
+    };
+    if let Some(previous_tunnel) = previous_tunnel {
+        previous_tunnel.close().await;
+    }
 
     Ok(())
 }
 
+#[tauri::command]
+pub async fn connect_to_database(
+    state: State<'_, AppState>,
+    id: String,
+) -> Result<(), CommandError> {
+    open_connection(&state, &id).await
+}
+
+/// Accepts an SSH host key the user checked, so the connection's tunnel
+/// trusts it from now on.
+#[tauri::command]
+pub async fn trust_host_key(
+    state: State<'_, AppState>,
+    id: String,
+    fingerprint: String,
+) -> Result<(), String> {
+    let mut store = state.connection_store.write().await;
+    let ssh = store
+        .connections
+        .iter_mut()
+        .find(|c| c.id == id)
+        .ok_or("Connection not found")?
+        .ssh
+        .as_mut()
+        .ok_or("Connection does not use an SSH tunnel")?;
+    ssh.host_key_fingerprint = Some(fingerprint);
+    store.save().map_err(|e| e.to_string())
+}
+
 #[tauri::command]
 pub async fn disconnect_from_database(
     state: State<'_, AppState>,
@@ -153,6 +534,11 @@ pub async fn disconnect_from_database(
     if let Some(conn) = active.remove(&id) {
         let _ = conn.close().await;
     }
+    drop(active);
+
+    if let Some(tunnel) = state.tunnels.write().await.remove(&id) {
+        tunnel.close().await;
+    }
     Ok(())
 }
 
@@ -214,7 +600,7 @@ pub async fn get_table_data(
     limit: i64,
     offset: i64,
     sort: Option<Vec<SortColumn>>,
-    filters: Option<Vec<FilterCondition>>,
+    filters: Option<FilterNode>,
 ) -> Result<TableData, String> {
     let active = state.active_connections.read().await;
     let conn = active.get(&connection_id).ok_or("No active connection")?;
@@ -237,13 +623,6 @@ pub struct SqlSort {
     pub direction: String,
 }
 
-#[derive(Debug, Deserialize)]
-pub struct SqlFilter {
-    pub column: String,
-    pub operator: String,
-    pub value: serde_json::Value,
-}
-
 #[tauri::command]
 pub async fn execute_query(
     state: State<'_, AppState>,
@@ -252,55 +631,36 @@ pub async fn execute_query(
     limit: Option<u32>,
     offset: Option<u32>,
     sort: Option<SqlSort>,
-    filters: Option<Vec<SqlFilter>>,
-) -> Result<QueryResult, String> {
+    params: Option<QueryParams>,
+    query_id: Option<String>,
+    timeout_ms: Option<u64>,
+    max_rows: Option<u64>,
+    confirmation_token: Option<String>,
+) -> Result<QueryResult, CommandError> {
+    let saved = saved_limits(&state, &connection_id).await;
+    let limits = QueryLimits {
+        timeout_ms: timeout_ms.or(saved.timeout_ms),
+        max_rows: max_rows.or(saved.max_rows),
+    };
+
     let active = state.active_connections.read().await;
     let conn = active.get(&connection_id).ok_or("No active connection")?;
 
-    let base_sql = sql.trim().trim_end_matches(';');
-
-    let mut clauses = Vec::new();
-
-    if let Some(filter_list) = &filters {
-        for f in filter_list {
-            let clause = match f.operator.as_str() {
-                "in" => {
-                    if let Some(arr) = f.value.as_array() {
-                        let vals: Vec<String> = arr
-                            .iter()
-                            .map(|v| format!("'{}'", v.as_str().unwrap_or("").replace('\'', "''")))
-                            .collect();
-                        format!("\"{}\" IN ({})", f.column, vals.join(","))
-                    } else {
-                        continue;
-                    }
-                }
-                "contains" => format!(
-                    "CAST(\"{}\" AS TEXT) ILIKE '%{}%'",
-                    f.column,
-                    f.value.as_str().unwrap_or("").replace('\'', "''")
-                ),
-                "equals" => {
-                    let val = f.value.as_str().unwrap_or("").replace('\'', "''");
-                    if val.parse::<f64>().is_ok() {
-                        format!("\"{}\" = {}", f.column, val)
-                    } else {
-                        format!("\"{}\" = '{}'", f.column, val)
-                    }
-                }
-                "isNull" => format!("\"{}\" IS NULL", f.column),
-                "isNotNull" => format!("\"{}\" IS NOT NULL", f.column),
-                _ => continue,
-            };
-            clauses.push(clause);
+    if is_production(&state, &connection_id).await {
+        if let Some(destructive) = find_destructive(&sql, conn.db_type()) {
+            require_confirmation(
+                &state,
+                conn.as_ref(),
+                &connection_id,
+                destructive,
+                &sql,
+                confirmation_token.as_deref(),
+            )
+            .await?;
         }
     }
 
-    let where_clause = if clauses.is_empty() {
-        String::new()
-    } else {
-        format!(" WHERE {}", clauses.join(" AND "))
-    };
+    let base_sql = sql.trim().trim_end_matches(';');
 
     let order_clause = if let Some(s) = &sort {
         format!(
@@ -319,18 +679,405 @@ pub async fn execute_query(
         String::new()
     };
 
-    let final_sql = if filters.is_some() || sort.is_some() {
+    // Only plain queries can be wrapped or paged; anything else runs as typed.
+    let is_select = classify(base_sql, conn.db_type()).kind == StatementKind::Select;
+    let final_sql = if !is_select {
+        sql.clone()
+    } else if sort.is_some() {
         format!(
-            "SELECT * FROM ({}) AS _subq{}{}{}",
-            base_sql, where_clause, order_clause, limit_clause
+            "SELECT * FROM ({}) AS _subq{}{}",
+            base_sql, order_clause, limit_clause
         )
     } else if limit.is_some() {
         format!("{}{}", base_sql, limit_clause)
     } else {
-        sql
+        sql.clone()
     };
 
-    conn.execute_query(&final_sql)
+    let start = Instant::now();
+    let result = match resolve_params(&final_sql, conn.db_type(), params) {
+        Ok((final_sql, params)) => {
+            conn.execute_query(&final_sql, &params, query_id.as_deref(), limits)
+                .await
+        }
+        Err(e) => Err(e),
+    }
+    .map_err(|e| e.to_string());
+
+    // Later pages of an already recorded query are not new executions.
+    if offset.unwrap_or(0) == 0 {
+        let (row_count, error) = match &result {
+            Ok(result) => (Some(result.rows_affected as i64), None),
+            Err(e) => (None, Some(e.as_str())),
+        };
+        let elapsed = start.elapsed().as_millis() as i64;
+        let _ = state
+            .history
+            .record(&connection_id, &sql, elapsed, row_count, error)
+            .await;
+    }
+    result.map_err(CommandError::from)
+}
+
+#[tauri::command]
+pub async fn execute_script(
+    state: State<'_, AppState>,
+    connection_id: String,
+    sql: String,
+    stop_on_error: Option<bool>,
+    confirmation_token: Option<String>,
+) -> Result<Vec<StatementResult>, CommandError> {
+    let active = state.active_connections.read().await;
+    let conn = active.get(&connection_id).ok_or("No active connection")?;
+
+    let statements = split_statements(&sql, conn.db_type());
+    if is_production(&state, &connection_id).await {
+        // One confirmation covers the whole script, listing every table any
+        // of its destructive statements touch.
+        let mut found: Option<Destructive> = None;
+        for statement in &statements {
+            let Some(destructive) = find_destructive(&statement.sql, conn.db_type()) else {
+                continue;
+            };
+            match &mut found {
+                None => found = Some(destructive),
+                Some(found) => {
+                    for table in destructive.tables {
+                        if !found.tables.contains(&table) {
+                            found.tables.push(table);
+                        }
+                    }
+                }
+            }
+        }
+        if let Some(destructive) = found {
+            require_confirmation(
+                &state,
+                conn.as_ref(),
+                &connection_id,
+                destructive,
+                &sql,
+                confirmation_token.as_deref(),
+            )
+            .await?;
+        }
+    }
+
+    conn.execute_script(&statements, stop_on_error.unwrap_or(true))
+        .await
+        .map_err(|e| CommandError::from(e.to_string()))
+}
+
+#[tauri::command]
+pub async fn search_query_history(
+    state: State<'_, AppState>,
+    filter: Option<HistoryFilter>,
+) -> Result<Vec<HistoryEntry>, String> {
+    state
+        .history
+        .search(&filter.unwrap_or_default())
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn prune_query_history(
+    state: State<'_, AppState>,
+    options: PruneOptions,
+) -> Result<u64, String> {
+    state
+        .history
+        .prune(&options)
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_history_entry(state: State<'_, AppState>, id: i64) -> Result<(), String> {
+    state.history.delete(id).await.map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SnippetInput {
+    pub name: String,
+    pub sql: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub folder: Option<String>,
+    #[serde(default)]
+    pub connection_id: Option<String>,
+}
+
+impl SnippetInput {
+    fn into_snippet(self) -> Snippet {
+        let mut snippet = Snippet::new(self.name, self.sql);
+        snippet.description = self.description;
+        snippet.tags = self.tags;
+        snippet.folder = self.folder.filter(|f| !f.trim_matches('/').is_empty());
+        snippet.connection_id = self.connection_id;
+        snippet
+    }
+}
+
+#[tauri::command]
+pub async fn get_snippets(
+    state: State<'_, AppState>,
+    connection_id: Option<String>,
+) -> Result<Vec<Snippet>, String> {
+    let store = state.snippet_store.read().await;
+    Ok(store.list(connection_id.as_deref()))
+}
+
+#[tauri::command]
+pub async fn save_snippet(
+    state: State<'_, AppState>,
+    input: SnippetInput,
+) -> Result<String, String> {
+    let snippet = input.into_snippet();
+    let id = snippet.id.clone();
+
+    let mut store = state.snippet_store.write().await;
+    store.add(snippet);
+    store.save().map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+#[tauri::command]
+pub async fn update_snippet(
+    state: State<'_, AppState>,
+    id: String,
+    input: SnippetInput,
+) -> Result<(), String> {
+    let mut snippet = input.into_snippet();
+    snippet.id = id;
+
+    let mut store = state.snippet_store.write().await;
+    store.update(snippet).map_err(|e| e.to_string())?;
+    store.save().map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+pub async fn delete_snippet(state: State<'_, AppState>, id: String) -> Result<(), String> {
+    let mut store = state.snippet_store.write().await;
+    store.remove(&id);
+    store.save().map_err(|e| e.to_string())
+}
+
+/// Imports from a JSON collection file or a directory of `.sql` files and
+/// returns the ids of the new snippets.
+#[tauri::command]
+pub async fn import_snippets(
+    state: State<'_, AppState>,
+    path: String,
+) -> Result<Vec<String>, String> {
+    let mut store = state.snippet_store.write().await;
+    let ids = store
+        .import(std::path::Path::new(&path))
+        .map_err(|e| e.to_string())?;
+    store.save().map_err(|e| e.to_string())?;
+    Ok(ids)
+}
+
+/// Exports to a JSON file (`format` "json") or a directory of `.sql` files
+/// (`format` "sql") and returns the number of snippets written.
+#[tauri::command]
+pub async fn export_snippets(
+    state: State<'_, AppState>,
+    path: String,
+    format: String,
+    ids: Option<Vec<String>>,
+) -> Result<usize, String> {
+    let as_sql_dir = match format.as_str() {
+        "json" => false,
+        "sql" => true,
+        _ => return Err(format!("Unsupported format: {}", format)),
+    };
+    let store = state.snippet_store.read().await;
+    store
+        .export(std::path::Path::new(&path), ids.as_deref(), as_sql_dir)
+        .map_err(|e| e.to_string())
+}
+
+/// Fills in the snippet's `{{placeholder}}` variables and runs the result
+/// through `execute_query`, so paging, parameters and history all apply.
+#[tauri::command]
+pub async fn execute_snippet(
+    state: State<'_, AppState>,
+    connection_id: String,
+    snippet_id: String,
+    variables: Option<HashMap<String, String>>,
+    limit: Option<u32>,
+    params: Option<QueryParams>,
+    query_id: Option<String>,
+    confirmation_token: Option<String>,
+) -> Result<QueryResult, CommandError> {
+    let sql = {
+        let store = state.snippet_store.read().await;
+        let snippet = store
+            .get(&snippet_id)
+            .ok_or_else(|| format!("Snippet not found: {}", snippet_id))?;
+        snippet
+            .render(&variables.unwrap_or_default())
+            .map_err(|e| e.to_string())?
+    };
+    execute_query(
+        state,
+        connection_id,
+        sql,
+        limit,
+        None,
+        None,
+        None,
+        params,
+        query_id,
+        None,
+        None,
+        confirmation_token,
+    )
+    .await
+}
+
+#[derive(Debug, Serialize)]
+pub struct DryRunResult {
+    pub rows_affected: u64,
+    /// Changed rows as returned by `RETURNING *`; empty on MySQL, which has
+    /// no such clause.
+    pub columns: Vec<ResultColumn>,
+    pub sample_rows: Vec<Vec<serde_json::Value>>,
+    pub execution_time_ms: u128,
+    pub messages: Vec<QueryMessage>,
+}
+
+const DRY_RUN_SAMPLE_SIZE: u64 = 20;
+
+/// Runs a data-modifying statement inside a transaction that is always
+/// rolled back, and reports what it would have changed.
+#[tauri::command]
+pub async fn dry_run_query(
+    state: State<'_, AppState>,
+    connection_id: String,
+    sql: String,
+    params: Option<QueryParams>,
+    sample_size: Option<u64>,
+) -> Result<DryRunResult, String> {
+    let active = state.active_connections.read().await;
+    let conn = active.get(&connection_id).ok_or("No active connection")?;
+    let db_type = conn.db_type();
+
+    let sql = sql.trim().trim_end_matches(';');
+    // SQLite runs every statement it is given, so a COMMIT further on would
+    // end the transaction that is meant to be rolled back.
+    if split_statements(sql, db_type).len() > 1 {
+        return Err("Dry runs only support a single statement".to_string());
+    }
+    if classify(sql, db_type).kind != StatementKind::Write {
+        return Err("Dry runs only support INSERT, UPDATE and DELETE statements".to_string());
+    }
+    // On its own line so that a trailing line comment cannot swallow it.
+    let sql = if accepts_returning(sql, db_type) {
+        format!("{}\nRETURNING *", sql)
+    } else {
+        sql.to_string()
+    };
+    let (sql, params) = resolve_params(&sql, db_type, params).map_err(|e| e.to_string())?;
+    let limits = QueryLimits {
+        max_rows: Some(sample_size.unwrap_or(DRY_RUN_SAMPLE_SIZE)),
+        ..saved_limits(&state, &connection_id).await
+    };
+
+    let result = conn
+        .dry_run(&sql, &params, limits)
+        .await
+        .map_err(|e| e.to_string())?;
+    Ok(DryRunResult {
+        rows_affected: result.rows_affected,
+        columns: result.columns,
+        sample_rows: result.rows,
+        execution_time_ms: result.execution_time_ms,
+        messages: result.messages,
+    })
+}
+
+#[tauri::command]
+pub async fn explain_query(
+    state: State<'_, AppState>,
+    connection_id: String,
+    sql: String,
+    analyze: Option<bool>,
+) -> Result<QueryPlan, String> {
+    let active = state.active_connections.read().await;
+    let conn = active.get(&connection_id).ok_or("No active connection")?;
+
+    conn.explain_query(&sql, analyze.unwrap_or(false))
+        .await
+        .map_err(|e| e.to_string())
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryStreamEvent {
+    pub query_id: String,
+    #[serde(flatten)]
+    pub event: QueryEvent,
+}
+
+#[tauri::command]
+pub async fn stream_query(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    connection_id: String,
+    sql: String,
+    query_id: String,
+    batch_size: Option<usize>,
+    confirmation_token: Option<String>,
+) -> Result<(), CommandError> {
+    let active = state.active_connections.read().await;
+    let conn = active.get(&connection_id).ok_or("No active connection")?;
+
+    if is_production(&state, &connection_id).await {
+        if let Some(destructive) = find_destructive(&sql, conn.db_type()) {
+            require_confirmation(
+                &state,
+                conn.as_ref(),
+                &connection_id,
+                destructive,
+                &sql,
+                confirmation_token.as_deref(),
+            )
+            .await?;
+        }
+    }
+
+    let mut on_event = |event: QueryEvent| {
+        let _ = app.emit(
+            "query-stream",
+            QueryStreamEvent {
+                query_id: query_id.clone(),
+                event,
+            },
+        );
+    };
+    conn.stream_query(
+        &sql,
+        &query_id,
+        batch_size.unwrap_or(500).max(1),
+        &mut on_event,
+    )
+    .await
+    .map_err(|e| CommandError::from(e.to_string()))
+}
+
+#[tauri::command]
+pub async fn cancel_query(
+    state: State<'_, AppState>,
+    connection_id: String,
+    query_id: String,
+) -> Result<(), String> {
+    let active = state.active_connections.read().await;
+    let conn = active.get(&connection_id).ok_or("No active connection")?;
+    conn.cancel_query(&query_id)
         .await
         .map_err(|e| e.to_string())
 }
@@ -401,12 +1148,41 @@ pub async fn drop_schema(
     connection_id: String,
     name: String,
     cascade: bool,
-) -> Result<(), String> {
+    confirmation_token: Option<String>,
+) -> Result<(), CommandError> {
     let active = state.active_connections.read().await;
     let conn = active.get(&connection_id).ok_or("No active connection")?;
+
+    if is_production(&state, &connection_id).await {
+        let tables: Vec<TableRef> = conn
+            .get_tables(&name)
+            .await
+            .map(|tables| {
+                tables
+                    .iter()
+                    .map(|t| TableRef::new(Some(&t.schema), &t.name))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let statement = format!(
+            "DROP SCHEMA {}{}",
+            name,
+            if cascade { " CASCADE" } else { "" }
+        );
+        require_confirmation(
+            &state,
+            conn.as_ref(),
+            &connection_id,
+            Destructive::new(DestructiveKind::Drop, tables),
+            &statement,
+            confirmation_token.as_deref(),
+        )
+        .await?;
+    }
+
     conn.drop_schema(&name, cascade)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| CommandError::from(e.to_string()))
 }
 
 #[tauri::command]
@@ -416,12 +1192,35 @@ pub async fn drop_table(
     schema: String,
     table: String,
     cascade: bool,
-) -> Result<(), String> {
+    confirmation_token: Option<String>,
+) -> Result<(), CommandError> {
     let active = state.active_connections.read().await;
     let conn = active.get(&connection_id).ok_or("No active connection")?;
+
+    if is_production(&state, &connection_id).await {
+        let statement = format!(
+            "DROP TABLE {}.{}{}",
+            schema,
+            table,
+            if cascade { " CASCADE" } else { "" }
+        );
+        require_confirmation(
+            &state,
+            conn.as_ref(),
+            &connection_id,
+            Destructive::new(
+                DestructiveKind::Drop,
+                vec![TableRef::new(Some(&schema), &table)],
+            ),
+            &statement,
+            confirmation_token.as_deref(),
+        )
+        .await?;
+    }
+
     conn.drop_table(&schema, &table, cascade)
         .await
-        .map_err(|e| e.to_string())
+        .map_err(|e| CommandError::from(e.to_string()))
 }
 
 #[tauri::command]
@@ -429,10 +1228,41 @@ pub async fn alter_table(
     state: State<'_, AppState>,
     connection_id: String,
     params: AlterTableParams,
-) -> Result<(), String> {
+    confirmation_token: Option<String>,
+) -> Result<(), CommandError> {
     let active = state.active_connections.read().await;
     let conn = active.get(&connection_id).ok_or("No active connection")?;
-    conn.alter_table(params).await.map_err(|e| e.to_string())
+
+    let dropped: Vec<String> = params
+        .changes
+        .iter()
+        .filter(|c| matches!(c.action, ColumnChangeAction::Drop))
+        .map(|c| format!("DROP COLUMN {}", c.column))
+        .collect();
+    if !dropped.is_empty() && is_production(&state, &connection_id).await {
+        let statement = format!(
+            "ALTER TABLE {}.{} {}",
+            params.schema,
+            params.table,
+            dropped.join(", ")
+        );
+        require_confirmation(
+            &state,
+            conn.as_ref(),
+            &connection_id,
+            Destructive::new(
+                DestructiveKind::DropColumn,
+                vec![TableRef::new(Some(&params.schema), &params.table)],
+            ),
+            &statement,
+            confirmation_token.as_deref(),
+        )
+        .await?;
+    }
+
+    conn.alter_table(params)
+        .await
+        .map_err(|e| CommandError::from(e.to_string()))
 }
 
 #[tauri::command]
@@ -445,9 +1275,13 @@ pub async fn export_data(
 ) -> Result<u64, String> {
     let active = state.active_connections.read().await;
     let conn = active.get(&connection_id).ok_or("No active connection")?;
+    // Exports skip the production confirmation, so they may only read.
+    if !allowed_read_only(&query, conn.db_type()) {
+        return Err("Exports only support queries that read data".to_string());
+    }
 
     let result = conn
-        .execute_query(&query)
+        .execute_query(&query, &[], None, QueryLimits::default())
         .await
         .map_err(|e| e.to_string())?;
 
@@ -457,7 +1291,7 @@ pub async fn export_data(
         "csv" => {
             let mut writer = csv::Writer::from_writer(file);
             writer
-                .write_record(&result.columns)
+                .write_record(result.columns.iter().map(|c| &c.name))
                 .map_err(|e| e.to_string())?;
 
             for row in &result.rows {
@@ -483,7 +1317,7 @@ pub async fn export_data(
                     let mut obj = serde_json::Map::new();
                     for (i, col) in result.columns.iter().enumerate() {
                         obj.insert(
-                            col.clone(),
+                            col.name.clone(),
                             row.get(i).cloned().unwrap_or(serde_json::Value::Null),
                         );
                     }
@@ -1,7 +1,25 @@
-use crate::db::{ConnectionFactory, DatabaseType};
+use crate::db::{ConnectionParams, DatabaseType, TlsConfig};
+use crate::secrets::{SecretKind, SecretStore, VaultFiles};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use uuid::Uuid;
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SshConfig {
+    pub host: String,
+    pub port: u16,
+    pub username: String,
+    #[serde(default, skip_serializing)]
+    pub password: String,
+    pub private_key_path: Option<String>,
+    #[serde(default, skip_serializing)]
+    pub passphrase: String,
+    /// SHA256 fingerprint of a host key the user accepted although it is not
+    /// in `known_hosts`.
+    #[serde(default)]
+    pub host_key_fingerprint: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SavedConnection {
     pub id: String,
@@ -11,7 +29,25 @@ pub struct SavedConnection {
     pub port: u16,
     pub database: String,
     pub username: String,
+    #[serde(default, skip_serializing)]
     pub password: String,
+    #[serde(default)]
+    pub ssh: Option<SshConfig>,
+    #[serde(default)]
+    pub tls: TlsConfig,
+    #[serde(default)]
+    pub parameters: HashMap<String, String>,
+    #[serde(default)]
+    pub read_only: bool,
+    /// Default statement timeout for ad-hoc queries on this connection.
+    #[serde(default)]
+    pub statement_timeout_ms: Option<u64>,
+    /// Default cap on the rows an ad-hoc query returns.
+    #[serde(default)]
+    pub max_rows: Option<u64>,
+    /// Destructive statements need an explicit confirmation when set.
+    #[serde(default)]
+    pub production: bool,
 }
 
 impl SavedConnection {
@@ -33,58 +69,271 @@ impl SavedConnection {
             database,
             username,
             password,
+            ssh: None,
+            tls: TlsConfig::default(),
+            parameters: HashMap::new(),
+            read_only: false,
+            statement_timeout_ms: None,
+            max_rows: None,
+            production: false,
         }
     }
 
-    pub fn connection_string(&self) -> String {
-        ConnectionFactory::build_connection_string(
-            self.db_type,
-            &self.host,
-            self.port,
-            &self.database,
-            &self.username,
-            &self.password,
-        )
+    /// Whether `updated` connects differently, as opposed to only changing
+    /// the name, statement limits or production flag. Secrets left empty keep
+    /// the stored ones, so only a newly entered secret counts as a change.
+    pub fn needs_reconnect(&self, updated: &SavedConnection) -> bool {
+        let tunnel = |conn: &SavedConnection| {
+            conn.ssh.as_ref().map(|ssh| {
+                (
+                    ssh.host.clone(),
+                    ssh.port,
+                    ssh.username.clone(),
+                    ssh.private_key_path.clone(),
+                )
+            })
+        };
+        let new_secret = !updated.password.is_empty()
+            || updated
+                .ssh
+                .as_ref()
+                .is_some_and(|ssh| !ssh.password.is_empty() || !ssh.passphrase.is_empty());
+
+        new_secret
+            || self.db_type != updated.db_type
+            || self.host != updated.host
+            || self.port != updated.port
+            || self.database != updated.database
+            || self.username != updated.username
+            || self.tls != updated.tls
+            || self.parameters != updated.parameters
+            || self.read_only != updated.read_only
+            || tunnel(self) != tunnel(updated)
+    }
+
+    pub fn connection_params(&self) -> ConnectionParams {
+        ConnectionParams {
+            host: self.host.clone(),
+            port: self.port,
+            database: self.database.clone(),
+            username: self.username.clone(),
+            password: self.password.clone(),
+            tls: self.tls.clone(),
+            parameters: self.parameters.clone(),
+            read_only: self.read_only,
+            socket: None,
+        }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, thiserror::Error)]
+pub enum StoreError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Vault error: {0}")]
+    Vault(#[from] tauri_plugin_stronghold::stronghold::Error),
+    #[error("Connection not found: {0}")]
+    NotFound(String),
+    #[error("The vault is locked")]
+    Locked,
+    #[error("Wrong master password")]
+    WrongPassword,
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct ConnectionsFile {
+    connections: Vec<SavedConnection>,
+}
+
 pub struct ConnectionStore {
     pub connections: Vec<SavedConnection>,
+    /// `None` until the user unlocks the vault with the master password.
+    secrets: Option<SecretStore>,
 }
 
 impl ConnectionStore {
     pub fn load() -> Self {
         let path = Self::config_path();
-        if path.exists() {
+        let file: ConnectionsFile = if path.exists() {
             let content = std::fs::read_to_string(&path).unwrap_or_default();
             serde_json::from_str(&content).unwrap_or_default()
         } else {
-            Self::default()
+            ConnectionsFile::default()
+        };
+
+        Self {
+            connections: file.connections,
+            secrets: None,
         }
     }
 
-    pub fn save(&self) -> Result<(), std::io::Error> {
+    pub fn is_unlocked(&self) -> bool {
+        self.secrets.is_some()
+    }
+
+    /// Opens the vault with the master password, creating it on first use,
+    /// and moves passwords still stored in plaintext into it.
+    pub fn unlock(&mut self, files: &VaultFiles, master_password: &str) -> Result<(), StoreError> {
+        let existed = files.exists();
+        let secrets = SecretStore::open(files, master_password).map_err(|e| {
+            if existed {
+                StoreError::WrongPassword
+            } else {
+                e.into()
+            }
+        })?;
+        self.secrets = Some(secrets);
+        self.migrate_plaintext_passwords()
+    }
+
+    fn secrets(&self) -> Result<&SecretStore, StoreError> {
+        self.secrets.as_ref().ok_or(StoreError::Locked)
+    }
+
+    pub fn save(&self) -> Result<(), StoreError> {
+        self.secrets()?.save()?;
+
         let path = Self::config_path();
         if let Some(parent) = path.parent() {
             std::fs::create_dir_all(parent)?;
         }
-        let content = serde_json::to_string_pretty(self)?;
-        std::fs::write(path, content)
+        let file = ConnectionsFile {
+            connections: self.connections.clone(),
+        };
+        let content = serde_json::to_string_pretty(&file).map_err(std::io::Error::from)?;
+        std::fs::write(path, content)?;
+        Ok(())
     }
 
-    pub fn add(&mut self, conn: SavedConnection) {
+    pub fn add(&mut self, mut conn: SavedConnection) -> Result<(), StoreError> {
+        Self::stash_secrets(self.secrets()?, &mut conn)?;
         self.connections.push(conn);
+        Ok(())
     }
 
-    pub fn remove(&mut self, id: &str) {
+    /// Replaces a saved connection. Secrets left empty keep the stored ones
+    /// unless their kind is listed in `cleared`.
+    pub fn update(
+        &mut self,
+        mut conn: SavedConnection,
+        cleared: &[SecretKind],
+    ) -> Result<(), StoreError> {
+        let index = self
+            .connections
+            .iter()
+            .position(|c| c.id == conn.id)
+            .ok_or_else(|| StoreError::NotFound(conn.id.clone()))?;
+        let secrets = self.secrets()?;
+        for &kind in cleared {
+            secrets.remove(&conn.id, kind)?;
+        }
+        if conn.ssh.is_none() {
+            secrets.remove(&conn.id, SecretKind::SshPassword)?;
+            secrets.remove(&conn.id, SecretKind::SshPassphrase)?;
+        }
+        Self::stash_secrets(secrets, &mut conn)?;
+        self.connections[index] = conn;
+        Ok(())
+    }
+
+    pub fn duplicate(&mut self, id: &str) -> Result<String, StoreError> {
+        let mut copy = self
+            .get(id)
+            .cloned()
+            .ok_or_else(|| StoreError::NotFound(id.to_string()))?;
+        self.resolve_secrets(&mut copy)?;
+        copy.id = Uuid::new_v4().to_string();
+        copy.name = format!("{} (copy)", copy.name);
+
+        let new_id = copy.id.clone();
+        self.add(copy)?;
+        Ok(new_id)
+    }
+
+    pub fn find_matching(&self, conn: &SavedConnection) -> Option<&SavedConnection> {
+        self.connections.iter().find(|c| {
+            c.db_type == conn.db_type
+                && c.host == conn.host
+                && c.port == conn.port
+                && c.database == conn.database
+                && c.username == conn.username
+        })
+    }
+
+    pub fn remove(&mut self, id: &str) -> Result<(), StoreError> {
+        let secrets = self.secrets()?;
+        for kind in [
+            SecretKind::Password,
+            SecretKind::SshPassword,
+            SecretKind::SshPassphrase,
+        ] {
+            secrets.remove(id, kind)?;
+        }
         self.connections.retain(|c| c.id != id);
+        Ok(())
     }
 
     pub fn get(&self, id: &str) -> Option<&SavedConnection> {
         self.connections.iter().find(|c| c.id == id)
     }
 
+    pub fn resolve_secrets(&self, conn: &mut SavedConnection) -> Result<(), StoreError> {
+        let secrets = self.secrets()?;
+        conn.password = secrets
+            .get(&conn.id, SecretKind::Password)?
+            .unwrap_or_default();
+        if let Some(ssh) = &mut conn.ssh {
+            ssh.password = secrets
+                .get(&conn.id, SecretKind::SshPassword)?
+                .unwrap_or_default();
+            ssh.passphrase = secrets
+                .get(&conn.id, SecretKind::SshPassphrase)?
+                .unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    fn stash_secrets(secrets: &SecretStore, conn: &mut SavedConnection) -> Result<(), StoreError> {
+        let id = conn.id.clone();
+        let mut fields = vec![(SecretKind::Password, &mut conn.password)];
+        if let Some(ssh) = &mut conn.ssh {
+            fields.push((SecretKind::SshPassword, &mut ssh.password));
+            fields.push((SecretKind::SshPassphrase, &mut ssh.passphrase));
+        }
+
+        for (kind, secret) in fields {
+            if !secret.is_empty() {
+                secrets.set(&id, kind, secret)?;
+                secret.clear();
+            }
+        }
+        Ok(())
+    }
+
+    fn has_plaintext_secrets(conn: &SavedConnection) -> bool {
+        !conn.password.is_empty()
+            || conn
+                .ssh
+                .as_ref()
+                .is_some_and(|ssh| !ssh.password.is_empty() || !ssh.passphrase.is_empty())
+    }
+
+    fn migrate_plaintext_passwords(&mut self) -> Result<(), StoreError> {
+        let secrets = self.secrets.as_ref().ok_or(StoreError::Locked)?;
+        let mut migrated = false;
+        for conn in &mut self.connections {
+            if Self::has_plaintext_secrets(conn) {
+                Self::stash_secrets(secrets, conn)?;
+                migrated = true;
+            }
+        }
+
+        if migrated {
+            self.save()?;
+        }
+        Ok(())
+    }
+
     fn config_path() -> std::path::PathBuf {
         dirs::config_dir()
             .unwrap_or_else(|| std::path::PathBuf::from("."))
@@ -0,0 +1,112 @@
+use super::traits::{DbError, DbResult};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::OwnedMutexGuard;
+
+struct RunningQuery<H> {
+    handle: H,
+    cancelled: bool,
+    /// Held while a cancel is being sent; `finish` waits for it.
+    cancelling: Arc<tokio::sync::Mutex<()>>,
+}
+
+/// Held by a driver until its interrupt has reached the server. Until then
+/// the query's connection is not released, so the interrupt cannot hit a
+/// statement that reused the connection after the query finished.
+pub struct PendingCancel {
+    _guard: OwnedMutexGuard<()>,
+}
+
+/// Tracks in-flight ad-hoc queries by id together with whatever the driver
+/// needs to interrupt them (a backend PID, a connection id, a raw handle).
+pub struct RunningQueries<H> {
+    queries: Mutex<HashMap<String, RunningQuery<H>>>,
+}
+
+impl<H> Default for RunningQueries<H> {
+    fn default() -> Self {
+        Self {
+            queries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl<H> RunningQueries<H> {
+    pub fn register(&self, query_id: &str, handle: H) {
+        self.queries.lock().unwrap().insert(
+            query_id.to_string(),
+            RunningQuery {
+                handle,
+                cancelled: false,
+                cancelling: Arc::default(),
+            },
+        );
+    }
+
+    /// Unregisters the query and turns its error into `DbError::Cancelled` if
+    /// it failed because `cancel` was called on it. Waits for a cancel that is
+    /// still being sent, so the caller keeps the connection until then.
+    pub async fn finish<T>(&self, query_id: &str, result: DbResult<T>) -> DbResult<T> {
+        let query = self.queries.lock().unwrap().remove(query_id);
+        let cancelled = match query {
+            Some(query) => {
+                drop(query.cancelling.lock().await);
+                query.cancelled
+            }
+            None => false,
+        };
+        match result {
+            Err(_) if cancelled => Err(DbError::Cancelled),
+            result => result,
+        }
+    }
+
+    /// Marks the query as cancelled and runs `interrupt` on its handle while
+    /// the registry is locked. An interrupt that goes out over another
+    /// connection is sent after this returns, while holding the returned
+    /// `PendingCancel`; that connection should be acquired beforehand so the
+    /// query is not kept waiting for the pool.
+    pub fn cancel<R>(
+        &self,
+        query_id: &str,
+        interrupt: impl FnOnce(&H) -> R,
+    ) -> DbResult<(R, PendingCancel)> {
+        let mut queries = self.queries.lock().unwrap();
+        let query = queries
+            .get_mut(query_id)
+            .ok_or_else(|| DbError::NotFound(format!("No running query with id {}", query_id)))?;
+        let pending = query.cancelling.clone().try_lock_owned().map_err(|_| {
+            DbError::InvalidOperation(format!("Query {} is already being cancelled", query_id))
+        })?;
+        query.cancelled = true;
+        Ok((interrupt(&query.handle), PendingCancel { _guard: pending }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn finish_waits_for_pending_cancel() {
+        let running = Arc::new(RunningQueries::default());
+        running.register("q", 7u32);
+        let (handle, pending) = running.cancel("q", |h| *h).unwrap();
+        assert_eq!(handle, 7);
+        assert!(running.cancel("q", |h| *h).is_err());
+
+        let finishing = tokio::spawn({
+            let running = running.clone();
+            async move {
+                running
+                    .finish::<()>("q", Err(DbError::Query("interrupted".into())))
+                    .await
+            }
+        });
+        tokio::task::yield_now().await;
+        assert!(!finishing.is_finished());
+
+        drop(pending);
+        assert!(matches!(finishing.await.unwrap(), Err(DbError::Cancelled)));
+    }
+}
@@ -0,0 +1,806 @@
+use super::script::split_statements;
+use super::traits::DatabaseType;
+use serde::Serialize;
+use sqlparser::ast::{
+    AlterTableOperation, Expr, FromTable, ObjectName, ObjectType, Query, SetExpr, Statement,
+    TableFactor, TableWithJoins, TransactionAccessMode, TransactionMode, Value,
+};
+use sqlparser::dialect::{Dialect, MySqlDialect, PostgreSqlDialect, SQLiteDialect};
+use sqlparser::parser::Parser;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatementKind {
+    /// SELECT, WITH, VALUES or TABLE; safe to wrap in a subquery.
+    Select,
+    /// SHOW, PRAGMA, EXPLAIN, DESCRIBE and similar row-returning commands.
+    Utility,
+    Write,
+    Ddl,
+    Other,
+}
+
+impl StatementKind {
+    pub fn modifies_data(self) -> bool {
+        matches!(self, StatementKind::Write | StatementKind::Ddl)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct StatementInfo {
+    pub kind: StatementKind,
+    pub returns_rows: bool,
+}
+
+impl StatementInfo {
+    fn new(kind: StatementKind, returns_rows: bool) -> Self {
+        Self { kind, returns_rows }
+    }
+}
+
+fn dialect(db_type: DatabaseType) -> Box<dyn Dialect> {
+    match db_type {
+        DatabaseType::PostgreSQL => Box::new(PostgreSqlDialect {}),
+        DatabaseType::MySQL => Box::new(MySqlDialect {}),
+        DatabaseType::SQLite => Box::new(SQLiteDialect {}),
+    }
+}
+
+/// Classifies the first statement in `sql`. Statements the parser cannot
+/// handle fall back to looking at their leading keyword.
+pub fn classify(sql: &str, db_type: DatabaseType) -> StatementInfo {
+    match Parser::parse_sql(dialect(db_type).as_ref(), sql) {
+        Ok(statements) if !statements.is_empty() => classify_statement(&statements[0]),
+        _ => classify_by_keyword(sql),
+    }
+}
+
+fn classify_statement(statement: &Statement) -> StatementInfo {
+    use StatementKind::*;
+    match statement {
+        Statement::Query(query) => match query.body.as_ref() {
+            SetExpr::Insert(inner) | SetExpr::Update(inner) => classify_statement(inner),
+            _ if !modifying_ctes(query).is_empty() => StatementInfo::new(Write, true),
+            _ => StatementInfo::new(Select, true),
+        },
+        Statement::Insert(insert) => StatementInfo::new(Write, insert.returning.is_some()),
+        Statement::Update { returning, .. } => StatementInfo::new(Write, returning.is_some()),
+        Statement::Delete(delete) => StatementInfo::new(Write, delete.returning.is_some()),
+        Statement::Merge { .. } | Statement::Copy { .. } | Statement::LoadData { .. } => {
+            StatementInfo::new(Write, false)
+        }
+        Statement::Explain {
+            analyze,
+            options,
+            statement,
+            ..
+        } => {
+            let analyze = *analyze
+                || options.iter().flatten().any(|option| {
+                    option.name.value.eq_ignore_ascii_case("analyze")
+                        && !matches!(option.arg, Some(Expr::Value(Value::Boolean(false))))
+                });
+            // EXPLAIN ANALYZE actually runs the statement.
+            let kind = if analyze {
+                classify_statement(statement).kind
+            } else {
+                Utility
+            };
+            StatementInfo::new(kind, true)
+        }
+        Statement::ExplainTable { .. }
+        | Statement::ShowFunctions { .. }
+        | Statement::ShowVariable { .. }
+        | Statement::ShowStatus { .. }
+        | Statement::ShowVariables { .. }
+        | Statement::ShowCreate { .. }
+        | Statement::ShowColumns { .. }
+        | Statement::ShowDatabases { .. }
+        | Statement::ShowSchemas { .. }
+        | Statement::ShowTables { .. }
+        | Statement::ShowViews { .. }
+        | Statement::ShowCollation { .. }
+        | Statement::Pragma { .. } => StatementInfo::new(Utility, true),
+        Statement::CreateView { .. }
+        | Statement::CreateTable(_)
+        | Statement::CreateVirtualTable { .. }
+        | Statement::CreateIndex(_)
+        | Statement::CreateRole { .. }
+        | Statement::CreatePolicy { .. }
+        | Statement::AlterTable { .. }
+        | Statement::AlterIndex { .. }
+        | Statement::AlterView { .. }
+        | Statement::AlterRole { .. }
+        | Statement::AlterPolicy { .. }
+        | Statement::Drop { .. }
+        | Statement::DropFunction { .. }
+        | Statement::DropProcedure { .. }
+        | Statement::DropPolicy { .. }
+        | Statement::DropTrigger { .. }
+        | Statement::CreateExtension { .. }
+        | Statement::Truncate { .. }
+        | Statement::Comment { .. }
+        | Statement::CreateSchema { .. }
+        | Statement::CreateDatabase { .. }
+        | Statement::CreateFunction(_)
+        | Statement::CreateTrigger { .. }
+        | Statement::CreateProcedure { .. }
+        | Statement::CreateSequence { .. }
+        | Statement::CreateType { .. }
+        | Statement::Grant { .. }
+        | Statement::Revoke { .. } => StatementInfo::new(Ddl, false),
+        _ => StatementInfo::new(Other, false),
+    }
+}
+
+/// Whether every statement in `sql` is known to leave data alone, so that it
+/// may run on a read-only connection. Besides reads this allows transaction
+/// control and session settings, except those that turn writes back on.
+/// CALL, DO and statements that cannot be classified may write.
+pub fn allowed_read_only(sql: &str, db_type: DatabaseType) -> bool {
+    split_statements(sql, db_type)
+        .iter()
+        .all(|statement| statement_allowed_read_only(&statement.sql, db_type))
+}
+
+fn statement_allowed_read_only(sql: &str, db_type: DatabaseType) -> bool {
+    let lower = sql.to_lowercase();
+    // set_config() changes a setting from inside a plain SELECT.
+    if lower.contains("set_config") && lower.contains("read_only") {
+        return false;
+    }
+    match Parser::parse_sql(dialect(db_type).as_ref(), sql) {
+        Ok(statements) if !statements.is_empty() => statements.iter().all(statement_is_read_only),
+        _ => read_only_by_keyword(&lower),
+    }
+}
+
+fn statement_is_read_only(statement: &Statement) -> bool {
+    use StatementKind::*;
+    let read_write = TransactionMode::AccessMode(TransactionAccessMode::ReadWrite);
+    match statement {
+        Statement::StartTransaction { modes, .. } | Statement::SetTransaction { modes, .. } => {
+            !modes.contains(&read_write)
+        }
+        Statement::SetVariable { variables, .. } => !variables.iter().any(|name| {
+            name.0
+                .iter()
+                .any(|part| part.value.to_lowercase().contains("read_only"))
+        }),
+        Statement::Commit { .. }
+        | Statement::Rollback { .. }
+        | Statement::Savepoint { .. }
+        | Statement::ReleaseSavepoint { .. }
+        | Statement::SetNames { .. }
+        | Statement::SetNamesDefault { .. }
+        | Statement::SetTimeZone { .. }
+        | Statement::SetRole { .. }
+        | Statement::Use(_) => true,
+        _ => matches!(classify_statement(statement).kind, Select | Utility),
+    }
+}
+
+fn read_only_by_keyword(lower: &str) -> bool {
+    use StatementKind::*;
+    match classify_by_keyword(lower).kind {
+        Select | Utility => true,
+        Write | Ddl => false,
+        Other => {
+            let keyword: String = strip_leading_comments(lower)
+                .chars()
+                .take_while(|c| c.is_ascii_alphabetic())
+                .collect();
+            let words = lower.split_whitespace().collect::<Vec<_>>().join(" ");
+            matches!(
+                keyword.as_str(),
+                "begin" | "start" | "commit" | "rollback" | "end" | "savepoint" | "release" | "set"
+            ) && !words.contains("read write")
+                && !words.contains("read_only")
+        }
+    }
+}
+
+/// INSERT and UPDATE statements run by the CTEs of `query`, including CTEs
+/// nested inside them.
+fn modifying_ctes(query: &Query) -> Vec<&Statement> {
+    let mut statements = Vec::new();
+    for cte in query.with.iter().flat_map(|with| &with.cte_tables) {
+        match cte.query.body.as_ref() {
+            SetExpr::Insert(inner) | SetExpr::Update(inner) => statements.push(inner),
+            _ => {}
+        }
+        statements.extend(modifying_ctes(&cte.query));
+    }
+    statements
+}
+
+/// Why a statement has to be confirmed on a production connection.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DestructiveKind {
+    UnfilteredUpdate,
+    UnfilteredDelete,
+    Truncate,
+    Drop,
+    DropColumn,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TableRef {
+    pub schema: Option<String>,
+    pub name: String,
+}
+
+impl TableRef {
+    pub fn new(schema: Option<&str>, name: &str) -> Self {
+        Self {
+            schema: schema.map(str::to_string),
+            name: name.to_string(),
+        }
+    }
+
+    fn from_object_name(name: &ObjectName) -> Option<Self> {
+        let mut parts = name.0.iter().rev().map(|ident| ident.value.as_str());
+        let table = parts.next()?;
+        Some(Self::new(parts.next(), table))
+    }
+}
+
+/// A statement that empties or removes data wholesale, with the tables whose
+/// rows it touches. `tables` is empty when they cannot be told from the SQL.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Destructive {
+    pub kind: DestructiveKind,
+    pub tables: Vec<TableRef>,
+}
+
+impl Destructive {
+    pub fn new(kind: DestructiveKind, tables: Vec<TableRef>) -> Self {
+        Self { kind, tables }
+    }
+}
+
+/// Finds the first UPDATE or DELETE without WHERE, TRUNCATE, DROP or ALTER
+/// TABLE that drops a column in `sql`. Statements the parser cannot handle are judged by their leading
+/// keyword, and an UPDATE or DELETE is then flagged unless it mentions WHERE.
+pub fn find_destructive(sql: &str, db_type: DatabaseType) -> Option<Destructive> {
+    match Parser::parse_sql(dialect(db_type).as_ref(), sql) {
+        Ok(statements) if !statements.is_empty() => {
+            statements.iter().find_map(destructive_statement)
+        }
+        _ => destructive_by_keyword(sql),
+    }
+}
+
+fn destructive_statement(statement: &Statement) -> Option<Destructive> {
+    use DestructiveKind::*;
+    match statement {
+        Statement::Query(query) => {
+            let body = match query.body.as_ref() {
+                SetExpr::Update(inner) => Some(inner),
+                _ => None,
+            };
+            modifying_ctes(query)
+                .into_iter()
+                .chain(body)
+                .find_map(destructive_statement)
+        }
+        Statement::Update {
+            table,
+            selection: None,
+            ..
+        } => Some(Destructive::new(
+            UnfilteredUpdate,
+            joined_tables(std::slice::from_ref(table)),
+        )),
+        Statement::Delete(delete) if delete.selection.is_none() => {
+            let from = match &delete.from {
+                FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from) => from,
+            };
+            Some(Destructive::new(UnfilteredDelete, joined_tables(from)))
+        }
+        Statement::Truncate { table_names, .. } => Some(Destructive::new(
+            Truncate,
+            table_names
+                .iter()
+                .filter_map(|target| TableRef::from_object_name(&target.name))
+                .collect(),
+        )),
+        Statement::Drop {
+            object_type, names, ..
+        } => {
+            let tables = match object_type {
+                ObjectType::Table => names
+                    .iter()
+                    .filter_map(TableRef::from_object_name)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            Some(Destructive::new(Drop, tables))
+        }
+        Statement::DropFunction { .. }
+        | Statement::DropProcedure { .. }
+        | Statement::DropPolicy { .. }
+        | Statement::DropTrigger { .. } => Some(Destructive::new(Drop, Vec::new())),
+        Statement::AlterTable {
+            name, operations, ..
+        } if operations
+            .iter()
+            .any(|op| matches!(op, AlterTableOperation::DropColumn { .. })) =>
+        {
+            Some(Destructive::new(
+                DropColumn,
+                TableRef::from_object_name(name).into_iter().collect(),
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn joined_tables(from: &[TableWithJoins]) -> Vec<TableRef> {
+    from.iter()
+        .filter_map(|table| match &table.relation {
+            TableFactor::Table { name, .. } => TableRef::from_object_name(name),
+            _ => None,
+        })
+        .collect()
+}
+
+fn destructive_by_keyword(sql: &str) -> Option<Destructive> {
+    use DestructiveKind::*;
+    let sql = strip_leading_comments(sql);
+    let keyword: String = sql
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_lowercase();
+    let lower = sql.to_lowercase();
+    let filtered = lower.contains("where");
+
+    let kind = match keyword.as_str() {
+        "update" if !filtered => UnfilteredUpdate,
+        "delete" if !filtered => UnfilteredDelete,
+        "with" => return destructive_cte_by_keyword(&lower),
+        "truncate" => Truncate,
+        "drop" => Drop,
+        "alter" if lower.contains(" drop ") => DropColumn,
+        _ => return None,
+    };
+    Some(Destructive::new(kind, Vec::new()))
+}
+
+/// Judges each DELETE or UPDATE in a WITH statement the parser could not
+/// read by the text up to the end of its enclosing parentheses, so a WHERE
+/// in the outer query does not count for a CTE.
+fn destructive_cte_by_keyword(lower: &str) -> Option<Destructive> {
+    use DestructiveKind::*;
+    let is_word = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+    let bytes = lower.as_bytes();
+    for (keyword, kind) in [("delete", UnfilteredDelete), ("update", UnfilteredUpdate)] {
+        for (start, _) in lower.match_indices(keyword) {
+            let end = start + keyword.len();
+            if (start > 0 && is_word(bytes[start - 1]))
+                || bytes.get(end).is_some_and(|&b| is_word(b))
+            {
+                continue;
+            }
+            // ON CONFLICT DO UPDATE and FOR UPDATE are not statements.
+            let before = lower[..start].trim_end();
+            if before.ends_with(" do") || before.ends_with(" for") || before.ends_with(" key") {
+                continue;
+            }
+            if !enclosing_group(&lower[end..]).contains("where") {
+                return Some(Destructive::new(kind, Vec::new()));
+            }
+        }
+    }
+    None
+}
+
+/// `sql` up to the parenthesis that closes the group it starts in.
+fn enclosing_group(sql: &str) -> &str {
+    let mut depth = 0usize;
+    for (i, c) in sql.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return &sql[..i],
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    sql
+}
+
+/// The tables a single INSERT, UPDATE or DELETE writes to, including every
+/// table joined into a multi-table UPDATE or DELETE. `None` when `sql` is not
+/// one such statement that the parser understands.
+pub fn written_tables(sql: &str, db_type: DatabaseType) -> Option<Vec<TableRef>> {
+    let statements = Parser::parse_sql(dialect(db_type).as_ref(), sql).ok()?;
+    let [statement] = statements.as_slice() else {
+        return None;
+    };
+    let all_joined = |from: &[TableWithJoins]| {
+        from.iter()
+            .flat_map(|table| {
+                std::iter::once(&table.relation).chain(table.joins.iter().map(|j| &j.relation))
+            })
+            .filter_map(|relation| match relation {
+                TableFactor::Table { name, .. } => TableRef::from_object_name(name),
+                _ => None,
+            })
+            .collect::<Vec<_>>()
+    };
+    match statement {
+        Statement::Insert(insert) => Some(
+            TableRef::from_object_name(&insert.table_name)
+                .into_iter()
+                .collect(),
+        ),
+        Statement::Update { table, .. } => Some(all_joined(std::slice::from_ref(table))),
+        Statement::Delete(delete) => {
+            let from = match &delete.from {
+                FromTable::WithFromKeyword(from) | FromTable::WithoutKeyword(from) => from,
+            };
+            Some(all_joined(from))
+        }
+        _ => None,
+    }
+}
+
+/// Whether `RETURNING *` can be appended to `sql`: a single INSERT, UPDATE
+/// or DELETE that has no RETURNING clause yet, on a backend that has one.
+pub fn accepts_returning(sql: &str, db_type: DatabaseType) -> bool {
+    if db_type == DatabaseType::MySQL {
+        return false;
+    }
+    match Parser::parse_sql(dialect(db_type).as_ref(), sql) {
+        Ok(statements) => {
+            matches!(statements.as_slice(), [statement] if lacks_returning(statement))
+        }
+        Err(_) => false,
+    }
+}
+
+fn lacks_returning(statement: &Statement) -> bool {
+    match statement {
+        Statement::Query(query) => match query.body.as_ref() {
+            SetExpr::Insert(inner) | SetExpr::Update(inner) => lacks_returning(inner),
+            _ => false,
+        },
+        Statement::Insert(insert) => insert.returning.is_none(),
+        Statement::Update { returning, .. } => returning.is_none(),
+        Statement::Delete(delete) => delete.returning.is_none(),
+        _ => false,
+    }
+}
+
+fn classify_by_keyword(sql: &str) -> StatementInfo {
+    use StatementKind::*;
+    let sql = strip_leading_comments(sql);
+    let keyword: String = sql
+        .chars()
+        .take_while(|c| c.is_ascii_alphabetic())
+        .collect::<String>()
+        .to_lowercase();
+
+    let lower = sql.to_lowercase();
+    let returns_rows = lower.contains("returning");
+    match keyword.as_str() {
+        // A data-modifying CTE only returns rows through RETURNING.
+        "with"
+            if ["insert into", "update ", "delete from"]
+                .iter()
+                .any(|dml| lower.contains(dml)) =>
+        {
+            StatementInfo::new(Write, returns_rows)
+        }
+        "select" | "with" | "values" | "table" => StatementInfo::new(Select, true),
+        "show" | "pragma" | "explain" | "describe" | "desc" => StatementInfo::new(Utility, true),
+        "insert" | "update" | "delete" | "merge" | "replace" | "upsert" | "copy" => {
+            StatementInfo::new(Write, returns_rows)
+        }
+        "create" | "alter" | "drop" | "truncate" | "rename" | "comment" | "grant" | "revoke" => {
+            StatementInfo::new(Ddl, false)
+        }
+        _ => StatementInfo::new(Other, false),
+    }
+}
+
+fn strip_leading_comments(mut sql: &str) -> &str {
+    loop {
+        sql = sql.trim_start();
+        if let Some(rest) = sql.strip_prefix("--").or_else(|| sql.strip_prefix('#')) {
+            sql = rest.find('\n').map_or("", |i| &rest[i..]);
+        } else if let Some(rest) = sql.strip_prefix("/*") {
+            sql = rest.find("*/").map_or("", |i| &rest[i + 2..]);
+        } else {
+            return sql;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DatabaseType::*;
+    use StatementKind::*;
+
+    #[test]
+    fn classifies_statements() {
+        let cases = [
+            ("SELECT 1", PostgreSQL, Select, true),
+            ("  -- leading comment\nSELECT 1", PostgreSQL, Select, true),
+            ("VALUES (1), (2)", PostgreSQL, Select, true),
+            (
+                "WITH x AS (SELECT 1) SELECT * FROM x",
+                PostgreSQL,
+                Select,
+                true,
+            ),
+            ("SHOW TABLES", MySQL, Utility, true),
+            ("PRAGMA table_info(t)", SQLite, Utility, true),
+            ("EXPLAIN SELECT 1", PostgreSQL, Utility, true),
+            ("EXPLAIN ANALYZE DELETE FROM t", PostgreSQL, Write, true),
+            (
+                "EXPLAIN (ANALYZE false) DELETE FROM t",
+                PostgreSQL,
+                Utility,
+                true,
+            ),
+            ("INSERT INTO t VALUES (1)", PostgreSQL, Write, false),
+            (
+                "INSERT INTO t VALUES (1) RETURNING id",
+                PostgreSQL,
+                Write,
+                true,
+            ),
+            ("UPDATE t SET a = 1 WHERE id = 2", MySQL, Write, false),
+            ("DELETE FROM t RETURNING *", SQLite, Write, true),
+            (
+                "WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d",
+                PostgreSQL,
+                Write,
+                true,
+            ),
+            (
+                "WITH x AS (SELECT 1) INSERT INTO t SELECT * FROM x",
+                PostgreSQL,
+                Write,
+                false,
+            ),
+            ("CREATE TABLE t (id INT)", SQLite, Ddl, false),
+            ("DROP TABLE t", MySQL, Ddl, false),
+            ("TRUNCATE t", PostgreSQL, Ddl, false),
+            ("BEGIN", PostgreSQL, Other, false),
+            // Not understood by the parser; judged by the leading keyword.
+            ("SELECT FROM WHERE", PostgreSQL, Select, true),
+            ("DESCRIBE t extra words (", MySQL, Utility, true),
+            ("UPSERT INTO t VALUES (1)", SQLite, Write, false),
+            (
+                "/* hint */ REPLACE INTO t VALUES (1) RETURNING (",
+                MySQL,
+                Write,
+                true,
+            ),
+            (
+                "WITH d AS (DELETE FROM t RETURNING *) SELECT ((",
+                PostgreSQL,
+                Write,
+                true,
+            ),
+            ("WITH x AS (SELECT 1) SELECT ((", PostgreSQL, Select, true),
+            ("RENAME TABLE a TO b, c TO", MySQL, Ddl, false),
+            ("VACUUM FULL t (", SQLite, Other, false),
+        ];
+        for (sql, db_type, kind, returns_rows) in cases {
+            assert_eq!(
+                classify(sql, db_type),
+                StatementInfo::new(kind, returns_rows),
+                "{}",
+                sql
+            );
+        }
+    }
+
+    #[test]
+    fn allows_only_reads_on_read_only_connections() {
+        let cases = [
+            ("SELECT * FROM t", PostgreSQL, true),
+            ("SHOW TABLES", MySQL, true),
+            ("BEGIN", PostgreSQL, true),
+            ("START TRANSACTION READ ONLY", MySQL, true),
+            ("COMMIT", SQLite, true),
+            ("SET search_path = app", PostgreSQL, true),
+            ("SHOW default_transaction_read_only", PostgreSQL, true),
+            ("SELECT 1; SELECT 2", MySQL, true),
+            ("DELETE FROM t", PostgreSQL, false),
+            ("SELECT 1; DELETE FROM t", MySQL, false),
+            ("CREATE TABLE t (a int)", SQLite, false),
+            // Statements that turn writes back on for the session.
+            ("SET default_transaction_read_only = off", PostgreSQL, false),
+            ("SET SESSION transaction_read_only = 0", MySQL, false),
+            ("SET @@session.transaction_read_only = OFF", MySQL, false),
+            ("START TRANSACTION READ WRITE", PostgreSQL, false),
+            ("START TRANSACTION READ WRITE", MySQL, false),
+            ("BEGIN READ WRITE", PostgreSQL, false),
+            ("SET SESSION TRANSACTION READ WRITE", MySQL, false),
+            (
+                "SET SESSION CHARACTERISTICS AS TRANSACTION READ WRITE",
+                PostgreSQL,
+                false,
+            ),
+            ("RESET default_transaction_read_only", PostgreSQL, false),
+            ("RESET ALL", PostgreSQL, false),
+            ("DISCARD ALL", PostgreSQL, false),
+            (
+                "SELECT set_config('default_transaction_read_only', 'off', false)",
+                PostgreSQL,
+                false,
+            ),
+            // Procedures and unknown statements may write.
+            ("CALL proc()", MySQL, false),
+            ("CALL proc()", PostgreSQL, false),
+            ("DO $$ BEGIN DELETE FROM t; END $$", PostgreSQL, false),
+            ("ATTACH DATABASE 'x.db' AS x", SQLite, false),
+            ("FROB t ((", PostgreSQL, false),
+        ];
+        for (sql, db_type, expected) in cases {
+            assert_eq!(allowed_read_only(sql, db_type), expected, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn finds_destructive_statements() {
+        use DestructiveKind::*;
+        let t = |name| TableRef::new(None, name);
+        let cases = [
+            ("SELECT * FROM t", PostgreSQL, None),
+            ("DELETE FROM t WHERE id = 1", PostgreSQL, None),
+            ("UPDATE t SET a = 1 WHERE id = 1", MySQL, None),
+            ("INSERT INTO t VALUES (1)", SQLite, None),
+            (
+                "DELETE FROM t",
+                PostgreSQL,
+                Some((UnfilteredDelete, vec![t("t")])),
+            ),
+            (
+                "DELETE FROM public.t",
+                PostgreSQL,
+                Some((UnfilteredDelete, vec![TableRef::new(Some("public"), "t")])),
+            ),
+            (
+                "UPDATE t SET a = 1",
+                SQLite,
+                Some((UnfilteredUpdate, vec![t("t")])),
+            ),
+            (
+                "TRUNCATE a, b",
+                PostgreSQL,
+                Some((Truncate, vec![t("a"), t("b")])),
+            ),
+            ("DROP TABLE a, b", MySQL, Some((Drop, vec![t("a"), t("b")]))),
+            ("DROP VIEW v", PostgreSQL, Some((Drop, vec![]))),
+            (
+                "ALTER TABLE public.t DROP COLUMN a",
+                PostgreSQL,
+                Some((DropColumn, vec![TableRef::new(Some("public"), "t")])),
+            ),
+            (
+                "ALTER TABLE t ADD COLUMN b int, DROP COLUMN a",
+                MySQL,
+                Some((DropColumn, vec![t("t")])),
+            ),
+            ("ALTER TABLE t ADD COLUMN b int", SQLite, None),
+            // Only the first destructive statement of a script is reported.
+            (
+                "SELECT 1; DELETE FROM a; TRUNCATE b",
+                PostgreSQL,
+                Some((UnfilteredDelete, vec![t("a")])),
+            ),
+            // The parser has no DELETE in CTEs, so these go by keyword, and
+            // a WHERE in the outer query does not filter the CTE.
+            (
+                "WITH d AS (DELETE FROM t RETURNING *) SELECT * FROM d WHERE x = 1",
+                PostgreSQL,
+                Some((UnfilteredDelete, vec![])),
+            ),
+            (
+                "WITH d AS (DELETE FROM t WHERE a = 1 RETURNING *) SELECT * FROM d",
+                PostgreSQL,
+                None,
+            ),
+            (
+                "WITH u AS (UPDATE t SET a = 1 RETURNING *) SELECT * FROM u",
+                PostgreSQL,
+                Some((UnfilteredUpdate, vec![t("t")])),
+            ),
+            // Not understood by the parser; judged by keyword.
+            (
+                "DELETE FROM t ((",
+                PostgreSQL,
+                Some((UnfilteredDelete, vec![])),
+            ),
+            ("DELETE FROM t WHERE ((", PostgreSQL, None),
+            ("-- note\nDROP SOMETHING ODD (", MySQL, Some((Drop, vec![]))),
+            ("ALTER TABLE t DROP a ((", MySQL, Some((DropColumn, vec![]))),
+            ("ALTER TABLE t ADD b ((", MySQL, None),
+            (
+                "WITH d AS (DELETE FROM t RETURNING *) SELECT (( WHERE",
+                PostgreSQL,
+                Some((UnfilteredDelete, vec![])),
+            ),
+            (
+                "WITH d AS (DELETE FROM t WHERE a RETURNING *) SELECT ((",
+                PostgreSQL,
+                None,
+            ),
+            (
+                "WITH x AS (SELECT * FROM t FOR UPDATE) SELECT ((",
+                PostgreSQL,
+                None,
+            ),
+            (
+                "WITH x AS (INSERT INTO t VALUES (1) ON CONFLICT DO UPDATE SET a = 1) SELECT ((",
+                PostgreSQL,
+                None,
+            ),
+            (
+                "WITH x AS (SELECT 1) SELECT * FROM updated ((",
+                PostgreSQL,
+                None,
+            ),
+        ];
+        for (sql, db_type, expected) in cases {
+            let expected = expected.map(|(kind, tables)| Destructive::new(kind, tables));
+            assert_eq!(find_destructive(sql, db_type), expected, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn finds_written_tables() {
+        let t = |name| TableRef::new(None, name);
+        let cases = [
+            (
+                "INSERT INTO db.t VALUES (?)",
+                Some(vec![TableRef::new(Some("db"), "t")]),
+            ),
+            ("UPDATE t SET a = 1 WHERE id = ?", Some(vec![t("t")])),
+            (
+                "UPDATE a JOIN b ON a.id = b.id SET b.x = 1",
+                Some(vec![t("a"), t("b")]),
+            ),
+            ("DELETE FROM t WHERE id = 1", Some(vec![t("t")])),
+            (
+                "DELETE a FROM a JOIN b ON a.id = b.id",
+                Some(vec![t("a"), t("b")]),
+            ),
+            ("SELECT * FROM t", None),
+            ("DELETE FROM a; DELETE FROM b", None),
+            ("DELETE FROM t ((", None),
+        ];
+        for (sql, expected) in cases {
+            assert_eq!(written_tables(sql, MySQL), expected, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn accepts_returning_on_single_writes() {
+        let cases = [
+            ("INSERT INTO t VALUES (1)", PostgreSQL, true),
+            ("UPDATE t SET a = 1", SQLite, true),
+            ("DELETE FROM t WHERE id = 1", PostgreSQL, true),
+            ("DELETE FROM t RETURNING id", PostgreSQL, false),
+            ("INSERT INTO t VALUES (1)", MySQL, false),
+            ("SELECT 1", PostgreSQL, false),
+            (
+                "INSERT INTO t VALUES (1); INSERT INTO t VALUES (2)",
+                PostgreSQL,
+                false,
+            ),
+            ("INSERT INTO t VALUES ((", PostgreSQL, false),
+        ];
+        for (sql, db_type, expected) in cases {
+            assert_eq!(accepts_returning(sql, db_type), expected, "{}", sql);
+        }
+    }
+}
@@ -0,0 +1,594 @@
+use super::traits::{DbError, DbResult};
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlanBuffers {
+    pub shared_hit: u64,
+    pub shared_read: u64,
+    pub shared_dirtied: u64,
+    pub shared_written: u64,
+    pub temp_read: u64,
+    pub temp_written: u64,
+}
+
+/// One operation in a query plan. Estimates are always filled in where the
+/// engine reports them; the actual figures only for ANALYZE runs.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct PlanNode {
+    pub node_type: String,
+    pub relation: Option<String>,
+    pub detail: Option<String>,
+    pub estimated_rows: Option<f64>,
+    pub actual_rows: Option<f64>,
+    pub startup_cost: Option<f64>,
+    pub total_cost: Option<f64>,
+    pub actual_time_ms: Option<f64>,
+    pub loops: Option<u64>,
+    pub buffers: Option<PlanBuffers>,
+    pub children: Vec<PlanNode>,
+}
+
+impl PlanNode {
+    fn new(node_type: impl Into<String>) -> Self {
+        Self {
+            node_type: node_type.into(),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct QueryPlan {
+    pub nodes: Vec<PlanNode>,
+    pub analyzed: bool,
+    pub planning_time_ms: Option<f64>,
+    pub execution_time_ms: Option<f64>,
+    /// The engine's own output, for showing the plan as text.
+    pub raw: String,
+}
+
+/// Reads a number that engines report either as JSON number or as string.
+fn number(value: Option<&Value>) -> Option<f64> {
+    match value? {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+fn string(value: Option<&Value>) -> Option<String> {
+    value.and_then(Value::as_str).map(str::to_string)
+}
+
+fn invalid_plan(engine: &str) -> DbError {
+    DbError::Query(format!("Unexpected {} plan output", engine))
+}
+
+/// Parses the output of `EXPLAIN (FORMAT JSON)`.
+pub fn parse_postgres(output: Value, analyzed: bool) -> DbResult<QueryPlan> {
+    let root = output
+        .as_array()
+        .and_then(|plans| plans.first())
+        .and_then(Value::as_object)
+        .ok_or_else(|| invalid_plan("PostgreSQL"))?;
+    let plan = root
+        .get("Plan")
+        .and_then(Value::as_object)
+        .ok_or_else(|| invalid_plan("PostgreSQL"))?;
+
+    Ok(QueryPlan {
+        nodes: vec![postgres_node(plan)],
+        analyzed,
+        planning_time_ms: number(root.get("Planning Time")),
+        execution_time_ms: number(root.get("Execution Time")),
+        raw: serde_json::to_string_pretty(&output).unwrap_or_default(),
+    })
+}
+
+fn postgres_node(plan: &Map<String, Value>) -> PlanNode {
+    let relation = string(plan.get("Relation Name")).map(|name| match string(plan.get("Schema")) {
+        Some(schema) => format!("{}.{}", schema, name),
+        None => name,
+    });
+    let detail: Vec<String> = [
+        "Index Cond",
+        "Recheck Cond",
+        "Hash Cond",
+        "Merge Cond",
+        "Join Filter",
+        "Filter",
+        "Sort Key",
+        "Group Key",
+    ]
+    .iter()
+    .filter_map(|key| {
+        let value = match plan.get(*key)? {
+            Value::Array(items) => items
+                .iter()
+                .filter_map(Value::as_str)
+                .collect::<Vec<_>>()
+                .join(", "),
+            value => value.as_str()?.to_string(),
+        };
+        Some(format!("{}: {}", key, value))
+    })
+    .collect();
+
+    let count = |key: &str| plan.get(key).and_then(Value::as_u64);
+    let buffers = plan.contains_key("Shared Hit Blocks").then(|| PlanBuffers {
+        shared_hit: count("Shared Hit Blocks").unwrap_or(0),
+        shared_read: count("Shared Read Blocks").unwrap_or(0),
+        shared_dirtied: count("Shared Dirtied Blocks").unwrap_or(0),
+        shared_written: count("Shared Written Blocks").unwrap_or(0),
+        temp_read: count("Temp Read Blocks").unwrap_or(0),
+        temp_written: count("Temp Written Blocks").unwrap_or(0),
+    });
+
+    PlanNode {
+        node_type: string(plan.get("Node Type")).unwrap_or_default(),
+        relation: relation.or_else(|| string(plan.get("Index Name"))),
+        detail: (!detail.is_empty()).then(|| detail.join("; ")),
+        estimated_rows: number(plan.get("Plan Rows")),
+        actual_rows: number(plan.get("Actual Rows")),
+        startup_cost: number(plan.get("Startup Cost")),
+        total_cost: number(plan.get("Total Cost")),
+        actual_time_ms: number(plan.get("Actual Total Time")),
+        loops: count("Actual Loops"),
+        buffers,
+        children: plan
+            .get("Plans")
+            .and_then(Value::as_array)
+            .map(|plans| {
+                plans
+                    .iter()
+                    .filter_map(Value::as_object)
+                    .map(postgres_node)
+                    .collect()
+            })
+            .unwrap_or_default(),
+    }
+}
+
+/// Parses the output of MySQL's `EXPLAIN FORMAT=JSON`.
+pub fn parse_mysql_json(output: &str) -> DbResult<QueryPlan> {
+    let value: Value = serde_json::from_str(output).map_err(|_| invalid_plan("MySQL"))?;
+    let block = value
+        .get("query_block")
+        .and_then(Value::as_object)
+        .ok_or_else(|| invalid_plan("MySQL"))?;
+
+    Ok(QueryPlan {
+        nodes: vec![mysql_block("Query block", block)],
+        analyzed: false,
+        planning_time_ms: None,
+        execution_time_ms: None,
+        raw: output.to_string(),
+    })
+}
+
+fn mysql_block(node_type: &str, block: &Map<String, Value>) -> PlanNode {
+    let cost_info = block.get("cost_info");
+    let mut node = PlanNode::new(node_type);
+    node.total_cost = number(cost_info.and_then(|c| c.get("query_cost")))
+        .or_else(|| number(cost_info.and_then(|c| c.get("sort_cost"))));
+    node.children = mysql_children(block);
+    node
+}
+
+fn mysql_table(table: &Map<String, Value>) -> PlanNode {
+    let access = string(table.get("access_type")).unwrap_or_else(|| "ALL".to_string());
+    let cost_info = table.get("cost_info");
+    let detail: Vec<String> = [("key", "Index"), ("attached_condition", "Condition")]
+        .iter()
+        .filter_map(|(key, label)| Some(format!("{}: {}", label, table.get(*key)?.as_str()?)))
+        .collect();
+
+    let mut node = PlanNode::new(format!("Table access ({})", access));
+    node.relation = string(table.get("table_name"));
+    node.detail = (!detail.is_empty()).then(|| detail.join("; "));
+    node.estimated_rows = number(table.get("rows_produced_per_join"));
+    node.total_cost = number(cost_info.and_then(|c| c.get("prefix_cost")));
+    node.children = mysql_children(table);
+    node
+}
+
+fn mysql_children(block: &Map<String, Value>) -> Vec<PlanNode> {
+    let mut children = Vec::new();
+    for (key, value) in block {
+        let object = match value {
+            Value::Object(object) => object,
+            Value::Array(items) => {
+                children.extend(mysql_array(key, items));
+                continue;
+            }
+            _ => continue,
+        };
+        let node = match key.as_str() {
+            "table" => mysql_table(object),
+            "query_block" => mysql_block("Query block", object),
+            "materialized_from_subquery" => mysql_block("Materialize", object),
+            "ordering_operation" => mysql_block("Sort", object),
+            "grouping_operation" => mysql_block("Group", object),
+            "duplicates_removal" => mysql_block("Remove duplicates", object),
+            "windowing" => mysql_block("Window", object),
+            "buffer_result" => mysql_block("Buffer", object),
+            "union_result" => {
+                let mut node = PlanNode::new("Union");
+                if let Some(Value::Array(specs)) = object.get("query_specifications") {
+                    node.children = mysql_array("query_specifications", specs);
+                }
+                node
+            }
+            _ => continue,
+        };
+        children.push(node);
+    }
+    children
+}
+
+fn mysql_array(key: &str, items: &[Value]) -> Vec<PlanNode> {
+    let objects = items.iter().filter_map(Value::as_object);
+    match key {
+        "nested_loop" => {
+            let mut node = PlanNode::new("Nested loop");
+            node.children = objects.flat_map(mysql_children).collect();
+            vec![node]
+        }
+        "query_specifications"
+        | "attached_subqueries"
+        | "optimized_away_subqueries"
+        | "order_by_subqueries"
+        | "group_by_subqueries"
+        | "select_list_subqueries" => objects.flat_map(mysql_children).collect(),
+        _ => Vec::new(),
+    }
+}
+
+/// Parses the indented tree printed by MySQL's `EXPLAIN ANALYZE`, e.g.
+/// `-> Table scan on t  (cost=0.35 rows=1) (actual time=0.02..0.03 rows=1 loops=1)`.
+pub fn parse_mysql_tree(output: &str) -> DbResult<QueryPlan> {
+    let mut roots: Vec<PlanNode> = Vec::new();
+    let mut stack: Vec<(usize, PlanNode)> = Vec::new();
+
+    for line in output.lines() {
+        let trimmed = line.trim_start();
+        let Some(text) = trimmed.strip_prefix("-> ") else {
+            // Long conditions wrap onto continuation lines.
+            if let Some((_, node)) = stack.last_mut() {
+                if let Some(detail) = node.detail.as_mut() {
+                    detail.push(' ');
+                    detail.push_str(trimmed.trim());
+                }
+            }
+            continue;
+        };
+        let indent = line.len() - trimmed.len();
+        while stack.last().is_some_and(|(depth, _)| *depth >= indent) {
+            let (_, node) = stack.pop().unwrap();
+            attach(&mut stack, &mut roots, node);
+        }
+        stack.push((indent, mysql_tree_node(text)));
+    }
+    while let Some((_, node)) = stack.pop() {
+        attach(&mut stack, &mut roots, node);
+    }
+
+    if roots.is_empty() {
+        return Err(invalid_plan("MySQL"));
+    }
+    let execution_time_ms = roots.first().and_then(|root| root.actual_time_ms);
+    Ok(QueryPlan {
+        nodes: roots,
+        analyzed: true,
+        planning_time_ms: None,
+        execution_time_ms,
+        raw: output.to_string(),
+    })
+}
+
+fn attach(stack: &mut [(usize, PlanNode)], roots: &mut Vec<PlanNode>, node: PlanNode) {
+    match stack.last_mut() {
+        Some((_, parent)) => parent.children.push(node),
+        None => roots.push(node),
+    }
+}
+
+fn mysql_tree_node(text: &str) -> PlanNode {
+    let (description, stats) = match text.find("  (") {
+        Some(i) => (&text[..i], &text[i..]),
+        None => (text, ""),
+    };
+    let (node_type, detail) = match description.split_once(": ") {
+        Some((node_type, detail)) => (node_type, Some(detail.to_string())),
+        None => (description, None),
+    };
+
+    let mut node = PlanNode::new(node_type);
+    node.detail = detail;
+    node.relation = [" scan on ", " lookup on ", " access on "]
+        .iter()
+        .find_map(|marker| {
+            let start = node_type.find(marker)? + marker.len();
+            node_type[start..].split_whitespace().next()
+        })
+        .map(str::to_string);
+
+    for group in stats.split('(').skip(1) {
+        let group = group.trim_end().trim_end_matches(')');
+        let actual = group.starts_with("actual time=");
+        for field in group.split_whitespace() {
+            let Some((key, value)) = field.split_once('=') else {
+                continue;
+            };
+            let last = value
+                .rsplit("..")
+                .next()
+                .and_then(|v| v.parse::<f64>().ok());
+            match (key, actual) {
+                ("cost", _) => {
+                    node.total_cost = last;
+                    if let Some((startup, _)) = value.split_once("..") {
+                        node.startup_cost = startup.parse().ok();
+                    }
+                }
+                ("rows", false) => node.estimated_rows = last,
+                ("rows", true) => node.actual_rows = last,
+                ("time", true) => node.actual_time_ms = last,
+                ("loops", true) => node.loops = value.parse().ok(),
+                _ => {}
+            }
+        }
+    }
+    node
+}
+
+/// Builds the tree from `EXPLAIN QUERY PLAN` rows of `(id, parent, detail)`.
+pub fn parse_sqlite(rows: Vec<(i64, i64, String)>) -> QueryPlan {
+    let raw = rows
+        .iter()
+        .map(|(_, _, detail)| detail.as_str())
+        .collect::<Vec<_>>()
+        .join("\n");
+    QueryPlan {
+        nodes: sqlite_children(&rows, 0),
+        analyzed: false,
+        planning_time_ms: None,
+        execution_time_ms: None,
+        raw,
+    }
+}
+
+fn sqlite_children(rows: &[(i64, i64, String)], parent: i64) -> Vec<PlanNode> {
+    rows.iter()
+        .filter(|(_, row_parent, _)| *row_parent == parent)
+        .map(|(id, _, detail)| {
+            let mut words = detail.split_whitespace();
+            let operation = words.next().unwrap_or_default();
+            let mut node = if matches!(operation, "SCAN" | "SEARCH") {
+                let mut node = PlanNode::new(operation);
+                node.relation = words.find(|word| *word != "TABLE").map(str::to_string);
+                node.detail = Some(detail.clone());
+                node
+            } else {
+                PlanNode::new(detail.as_str())
+            };
+            node.children = sqlite_children(rows, *id);
+            node
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// One line per node: indented type, relation and detail.
+    fn outline(nodes: &[PlanNode]) -> Vec<String> {
+        fn walk(nodes: &[PlanNode], depth: usize, lines: &mut Vec<String>) {
+            for node in nodes {
+                lines.push(format!(
+                    "{}{} | {} | {}",
+                    "  ".repeat(depth),
+                    node.node_type,
+                    node.relation.as_deref().unwrap_or("-"),
+                    node.detail.as_deref().unwrap_or("-"),
+                ));
+                walk(&node.children, depth + 1, lines);
+            }
+        }
+        let mut lines = Vec::new();
+        walk(nodes, 0, &mut lines);
+        lines
+    }
+
+    #[test]
+    fn parses_postgres_plans() {
+        let output = json!([{
+            "Plan": {
+                "Node Type": "Hash Join",
+                "Hash Cond": "(o.user_id = u.id)",
+                "Startup Cost": 1.5,
+                "Total Cost": 40.25,
+                "Plan Rows": 12,
+                "Actual Rows": 10,
+                "Actual Total Time": 2.1,
+                "Actual Loops": 1,
+                "Shared Hit Blocks": 7,
+                "Shared Read Blocks": 2,
+                "Plans": [
+                    {
+                        "Node Type": "Seq Scan",
+                        "Relation Name": "orders",
+                        "Schema": "public",
+                        "Filter": "(total > 10)",
+                    },
+                    {
+                        "Node Type": "Hash",
+                        "Plans": [{
+                            "Node Type": "Index Only Scan",
+                            "Index Name": "users_pkey",
+                            "Sort Key": ["u.id", "u.name"],
+                        }],
+                    },
+                ],
+            },
+            "Planning Time": 0.2,
+            "Execution Time": 2.5,
+        }]);
+        let plan = parse_postgres(output, true).unwrap();
+        assert_eq!(
+            outline(&plan.nodes),
+            [
+                "Hash Join | - | Hash Cond: (o.user_id = u.id)",
+                "  Seq Scan | public.orders | Filter: (total > 10)",
+                "  Hash | - | -",
+                "    Index Only Scan | users_pkey | Sort Key: u.id, u.name",
+            ]
+        );
+        let root = &plan.nodes[0];
+        assert_eq!(root.startup_cost, Some(1.5));
+        assert_eq!(root.total_cost, Some(40.25));
+        assert_eq!(root.estimated_rows, Some(12.0));
+        assert_eq!(root.actual_rows, Some(10.0));
+        assert_eq!(root.actual_time_ms, Some(2.1));
+        assert_eq!(root.loops, Some(1));
+        let buffers = root.buffers.as_ref().unwrap();
+        assert_eq!((buffers.shared_hit, buffers.shared_read), (7, 2));
+        assert!(root.children[0].buffers.is_none());
+        assert_eq!(plan.planning_time_ms, Some(0.2));
+        assert_eq!(plan.execution_time_ms, Some(2.5));
+        assert!(plan.analyzed);
+
+        for output in [json!({}), json!([]), json!([{ "Plan": 1 }])] {
+            assert!(parse_postgres(output.clone(), false).is_err(), "{}", output);
+        }
+    }
+
+    #[test]
+    fn parses_mysql_json_plans() {
+        let cases = [
+            (
+                json!({
+                    "query_block": {
+                        "select_id": 1,
+                        "cost_info": { "query_cost": "2.75" },
+                        "nested_loop": [
+                            { "table": {
+                                "table_name": "o",
+                                "access_type": "ALL",
+                                "rows_produced_per_join": 10,
+                                "cost_info": { "prefix_cost": "1.25" },
+                                "attached_condition": "(o.total > 10)",
+                            } },
+                            { "table": {
+                                "table_name": "u",
+                                "access_type": "eq_ref",
+                                "key": "PRIMARY",
+                                "rows_produced_per_join": 1,
+                                "cost_info": { "prefix_cost": "2.75" },
+                            } },
+                        ],
+                    },
+                }),
+                vec![
+                    "Query block | - | -",
+                    "  Nested loop | - | -",
+                    "    Table access (ALL) | o | Condition: (o.total > 10)",
+                    "    Table access (eq_ref) | u | Index: PRIMARY",
+                ],
+                Some(2.75),
+            ),
+            (
+                json!({
+                    "query_block": {
+                        "ordering_operation": {
+                            "using_filesort": true,
+                            "cost_info": { "sort_cost": "1.00" },
+                            "table": { "table_name": "t" },
+                        },
+                    },
+                }),
+                vec![
+                    "Query block | - | -",
+                    "  Sort | - | -",
+                    "    Table access (ALL) | t | -",
+                ],
+                None,
+            ),
+        ];
+        for (output, expected, cost) in cases {
+            let plan = parse_mysql_json(&output.to_string()).unwrap();
+            assert_eq!(outline(&plan.nodes), expected, "{}", output);
+            assert_eq!(plan.nodes[0].total_cost, cost, "{}", output);
+            assert!(!plan.analyzed);
+        }
+
+        for output in ["not json", "{}", r#"{"query_block": 1}"#] {
+            assert!(parse_mysql_json(output).is_err(), "{}", output);
+        }
+    }
+
+    #[test]
+    fn parses_mysql_analyze_trees() {
+        let output = "\
+-> Nested loop inner join  (cost=2.50 rows=3) (actual time=0.050..0.120 rows=3 loops=1)
+    -> Filter: (o.total > 10)  (cost=1.25 rows=3) (actual time=0.030..0.060 rows=3 loops=1)
+        -> Table scan on o  (cost=1.25 rows=10) (actual time=0.020..0.050 rows=10 loops=1)
+    -> Single-row index lookup on u using PRIMARY (id=o.user_id)  (cost=0.25..0.35 rows=1) (actual time=0.010..0.010 rows=1 loops=3)
+";
+        let plan = parse_mysql_tree(output).unwrap();
+        assert_eq!(
+            outline(&plan.nodes),
+            [
+                "Nested loop inner join | - | -",
+                "  Filter | - | (o.total > 10)",
+                "    Table scan on o | o | -",
+                "  Single-row index lookup on u using PRIMARY (id=o.user_id) | u | -",
+            ]
+        );
+        let root = &plan.nodes[0];
+        assert_eq!(root.total_cost, Some(2.5));
+        assert_eq!(root.startup_cost, None);
+        assert_eq!(root.estimated_rows, Some(3.0));
+        assert_eq!(root.actual_rows, Some(3.0));
+        assert_eq!(root.actual_time_ms, Some(0.12));
+        let lookup = &root.children[1];
+        assert_eq!(lookup.startup_cost, Some(0.25));
+        assert_eq!(lookup.total_cost, Some(0.35));
+        assert_eq!(lookup.loops, Some(3));
+        assert_eq!(plan.execution_time_ms, Some(0.12));
+        assert!(plan.analyzed);
+
+        for output in ["", "EXPLAIN output without nodes"] {
+            assert!(parse_mysql_tree(output).is_err(), "{:?}", output);
+        }
+    }
+
+    #[test]
+    fn parses_sqlite_plans() {
+        let rows = [
+            (2, 0, "SCAN orders"),
+            (5, 0, "SEARCH users USING INTEGER PRIMARY KEY (rowid=?)"),
+            (7, 0, "CO-ROUTINE recent"),
+            (9, 7, "SCAN TABLE events"),
+            (12, 0, "USE TEMP B-TREE FOR ORDER BY"),
+        ]
+        .into_iter()
+        .map(|(id, parent, detail)| (id, parent, detail.to_string()))
+        .collect();
+        let plan = parse_sqlite(rows);
+        assert_eq!(
+            outline(&plan.nodes),
+            [
+                "SCAN | orders | SCAN orders",
+                "SEARCH | users | SEARCH users USING INTEGER PRIMARY KEY (rowid=?)",
+                "CO-ROUTINE recent | - | -",
+                "  SCAN | events | SCAN TABLE events",
+                "USE TEMP B-TREE FOR ORDER BY | - | -",
+            ]
+        );
+        assert_eq!(plan.raw.lines().count(), 5);
+    }
+}
@@ -0,0 +1,660 @@
+use super::traits::{
+    ColumnInfo, DatabaseType, DbError, DbResult, FilterCombinator, FilterCondition, FilterGroup,
+    FilterNode, FilterOperator, ParamValue,
+};
+
+/// Compiles a grid filter tree into a `WHERE` clause and the values to bind
+/// to its placeholders, in order. Column names are checked against the table
+/// and values are always bound, so nothing the user typed reaches the SQL
+/// text. Empty groups are ignored, so a tree without conditions compiles to
+/// an empty clause.
+pub fn compile_filters(
+    filters: &FilterNode,
+    columns: &[ColumnInfo],
+    db_type: DatabaseType,
+) -> DbResult<(String, Vec<ParamValue>)> {
+    let mut compiler = Compiler {
+        db_type,
+        columns,
+        values: Vec::new(),
+    };
+    let clause = match compiler.node(filters)? {
+        Some(condition) => format!("WHERE {}", condition),
+        None => String::new(),
+    };
+    Ok((clause, compiler.values))
+}
+
+/// `LIKE` escape character; chosen over backslash, which MySQL also treats
+/// as an escape inside string literals.
+const LIKE_ESCAPE: char = '!';
+
+struct Compiler<'a> {
+    db_type: DatabaseType,
+    columns: &'a [ColumnInfo],
+    values: Vec<ParamValue>,
+}
+
+impl Compiler<'_> {
+    fn node(&mut self, node: &FilterNode) -> DbResult<Option<String>> {
+        match node {
+            FilterNode::Group(group) => self.group(group),
+            FilterNode::Condition(filter) => {
+                let column = self
+                    .columns
+                    .iter()
+                    .find(|c| c.name == filter.column)
+                    .ok_or_else(|| {
+                        DbError::InvalidOperation(format!("Unknown column \"{}\"", filter.column))
+                    })?;
+                self.condition(column, filter).map(Some)
+            }
+        }
+    }
+
+    fn group(&mut self, group: &FilterGroup) -> DbResult<Option<String>> {
+        let mut conditions = Vec::with_capacity(group.children.len());
+        for child in &group.children {
+            if let Some(condition) = self.node(child)? {
+                conditions.push(condition);
+            }
+        }
+        if conditions.is_empty() {
+            return Ok(None);
+        }
+        let separator = match group.combinator {
+            FilterCombinator::And => " AND ",
+            FilterCombinator::Or => " OR ",
+        };
+        let joined = conditions.join(separator);
+        Ok(Some(match (group.negated, conditions.len()) {
+            (true, _) => format!("NOT ({})", joined),
+            (false, 1) => joined,
+            (false, _) => format!("({})", joined),
+        }))
+    }
+
+    fn condition(&mut self, column: &ColumnInfo, filter: &FilterCondition) -> DbResult<String> {
+        let target = self.column(column);
+        let value = filter.value.as_str();
+        Ok(match filter.operator {
+            FilterOperator::Equals => format!("{} = {}", target, self.bind(column, value)?),
+            FilterOperator::NotEquals => format!("{} <> {}", target, self.bind(column, value)?),
+            FilterOperator::GreaterThan => format!("{} > {}", target, self.bind(column, value)?),
+            FilterOperator::GreaterThanOrEqual => {
+                format!("{} >= {}", target, self.bind(column, value)?)
+            }
+            FilterOperator::LessThan => format!("{} < {}", target, self.bind(column, value)?),
+            FilterOperator::LessThanOrEqual => {
+                format!("{} <= {}", target, self.bind(column, value)?)
+            }
+            FilterOperator::Contains => {
+                self.like(column, &format!("%{}%", escape_like(value)), false)
+            }
+            FilterOperator::StartsWith => {
+                self.like(column, &format!("{}%", escape_like(value)), false)
+            }
+            FilterOperator::EndsWith => {
+                self.like(column, &format!("%{}", escape_like(value)), false)
+            }
+            FilterOperator::NotContains => {
+                self.like(column, &format!("%{}%", escape_like(value)), true)
+            }
+            FilterOperator::Like => self.like(column, value, false),
+            FilterOperator::NotLike => self.like(column, value, true),
+            FilterOperator::In | FilterOperator::NotIn => {
+                let negated = matches!(filter.operator, FilterOperator::NotIn);
+                if filter.values.is_empty() {
+                    // Nothing is in an empty list.
+                    return Ok(if negated { "1 = 1" } else { "1 = 0" }.to_string());
+                }
+                let list = filter
+                    .values
+                    .iter()
+                    .map(|v| self.bind(column, v))
+                    .collect::<DbResult<Vec<_>>>()?;
+                let keyword = if negated { "NOT IN" } else { "IN" };
+                format!("{} {} ({})", target, keyword, list.join(", "))
+            }
+            FilterOperator::Between => {
+                let [low, high] = filter.values.as_slice() else {
+                    return Err(DbError::InvalidOperation(format!(
+                        "BETWEEN on \"{}\" needs exactly two values",
+                        column.name
+                    )));
+                };
+                let low = self.bind(column, low)?;
+                let high = self.bind(column, high)?;
+                format!("{} BETWEEN {} AND {}", target, low, high)
+            }
+            FilterOperator::Regex => self.regex(column, value),
+            FilterOperator::IsNull => format!("{} IS NULL", target),
+            FilterOperator::IsNotNull => format!("{} IS NOT NULL", target),
+            FilterOperator::IsEmpty => self.is_empty(column),
+            FilterOperator::WithinLast => {
+                let span = RelativeSpan::parse(value)?;
+                let ago = self.relative(span, false);
+                format!("{} BETWEEN {} AND {}", target, ago, self.now())
+            }
+            FilterOperator::WithinNext => {
+                let span = RelativeSpan::parse(value)?;
+                let ahead = self.relative(span, true);
+                format!("{} BETWEEN {} AND {}", target, self.now(), ahead)
+            }
+            FilterOperator::OlderThan => {
+                let span = RelativeSpan::parse(value)?;
+                format!("{} < {}", target, self.relative(span, false))
+            }
+        })
+    }
+
+    /// The column as it appears on the left of a comparison. PostgreSQL
+    /// columns without a castable type name are compared as text.
+    fn column(&self, column: &ColumnInfo) -> String {
+        let quoted = quote_ident(&column.name, self.db_type);
+        match self.db_type {
+            DatabaseType::PostgreSQL if pg_cast_type(&column.data_type).is_none() => {
+                format!("{}::text", quoted)
+            }
+            _ => quoted,
+        }
+    }
+
+    /// Binds a value for comparison with `column`. PostgreSQL receives text
+    /// and casts it to the column type on the server; MySQL and SQLite get a
+    /// value of the matching kind.
+    fn bind(&mut self, column: &ColumnInfo, raw: &str) -> DbResult<String> {
+        match self.db_type {
+            DatabaseType::PostgreSQL => {
+                let placeholder = self.placeholder(ParamValue::Text(raw.to_string()));
+                Ok(match pg_cast_type(&column.data_type) {
+                    Some(cast) => format!("CAST({} AS {})", placeholder, cast),
+                    None => placeholder,
+                })
+            }
+            DatabaseType::MySQL | DatabaseType::SQLite => {
+                let value = typed_value(column, raw)?;
+                Ok(self.placeholder(value))
+            }
+        }
+    }
+
+    /// Matches the column's text form against a `LIKE` pattern, ignoring case
+    /// on every backend.
+    fn like(&mut self, column: &ColumnInfo, pattern: &str, negated: bool) -> String {
+        let quoted = quote_ident(&column.name, self.db_type);
+        let placeholder = self.placeholder(ParamValue::Text(pattern.to_string()));
+        let not = if negated { "NOT " } else { "" };
+        match self.db_type {
+            DatabaseType::PostgreSQL => format!(
+                "{}::text {}ILIKE {} ESCAPE '{}'",
+                quoted, not, placeholder, LIKE_ESCAPE
+            ),
+            // LIKE already ignores case under MySQL's default collations and
+            // for ASCII text in SQLite.
+            DatabaseType::MySQL | DatabaseType::SQLite => format!(
+                "{} {}LIKE {} ESCAPE '{}'",
+                quoted, not, placeholder, LIKE_ESCAPE
+            ),
+        }
+    }
+
+    /// Case sensitivity follows the backend: PostgreSQL and SQLite match
+    /// case, MySQL follows the column collation.
+    fn regex(&mut self, column: &ColumnInfo, pattern: &str) -> String {
+        let quoted = quote_ident(&column.name, self.db_type);
+        let placeholder = self.placeholder(ParamValue::Text(pattern.to_string()));
+        match self.db_type {
+            DatabaseType::PostgreSQL => format!("{}::text ~ {}", quoted, placeholder),
+            // SQLite has no REGEXP of its own; the connection registers one.
+            DatabaseType::MySQL | DatabaseType::SQLite => {
+                format!("{} REGEXP {}", quoted, placeholder)
+            }
+        }
+    }
+
+    /// NULL or an empty string. The column is compared as text so that a
+    /// numeric zero does not count as empty on MySQL.
+    fn is_empty(&self, column: &ColumnInfo) -> String {
+        let quoted = quote_ident(&column.name, self.db_type);
+        match self.db_type {
+            DatabaseType::PostgreSQL => format!("COALESCE({}::text, '') = ''", quoted),
+            DatabaseType::MySQL => format!("COALESCE(CAST({} AS CHAR), '') = ''", quoted),
+            DatabaseType::SQLite => format!("COALESCE(CAST({} AS TEXT), '') = ''", quoted),
+        }
+    }
+
+    fn now(&self) -> &'static str {
+        match self.db_type {
+            DatabaseType::PostgreSQL => "CURRENT_TIMESTAMP",
+            DatabaseType::MySQL => "NOW()",
+            DatabaseType::SQLite => "datetime('now')",
+        }
+    }
+
+    /// The current time moved `span` into the past, or into the future when
+    /// `ahead` is set.
+    fn relative(&mut self, span: RelativeSpan, ahead: bool) -> String {
+        let sign = if ahead { '+' } else { '-' };
+        match self.db_type {
+            DatabaseType::PostgreSQL => {
+                let interval = format!("{} {}s", span.amount, span.unit.keyword());
+                let placeholder = self.placeholder(ParamValue::Text(interval));
+                format!("{} {} CAST({} AS interval)", self.now(), sign, placeholder)
+            }
+            // The unit is a keyword from a closed set, only the amount is bound.
+            DatabaseType::MySQL => {
+                let placeholder = self.placeholder(ParamValue::Int(span.amount));
+                format!(
+                    "{} {} INTERVAL {} {}",
+                    self.now(),
+                    sign,
+                    placeholder,
+                    span.unit.keyword().to_uppercase()
+                )
+            }
+            // SQLite modifiers have no weeks.
+            DatabaseType::SQLite => {
+                let (amount, unit) = match span.unit {
+                    DateUnit::Week => (span.amount * 7, DateUnit::Day.keyword()),
+                    other => (span.amount, other.keyword()),
+                };
+                let modifier = format!("{}{} {}s", sign, amount, unit);
+                let placeholder = self.placeholder(ParamValue::Text(modifier));
+                format!("datetime('now', {})", placeholder)
+            }
+        }
+    }
+
+    fn placeholder(&mut self, value: ParamValue) -> String {
+        self.values.push(value);
+        match self.db_type {
+            DatabaseType::PostgreSQL => format!("${}", self.values.len()),
+            DatabaseType::MySQL | DatabaseType::SQLite => "?".to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum DateUnit {
+    Minute,
+    Hour,
+    Day,
+    Week,
+    Month,
+    Year,
+}
+
+impl DateUnit {
+    fn keyword(self) -> &'static str {
+        match self {
+            DateUnit::Minute => "minute",
+            DateUnit::Hour => "hour",
+            DateUnit::Day => "day",
+            DateUnit::Week => "week",
+            DateUnit::Month => "month",
+            DateUnit::Year => "year",
+        }
+    }
+}
+
+/// A span of time relative to now, written like `7 days` or `1 month`.
+#[derive(Debug, Clone, Copy)]
+struct RelativeSpan {
+    amount: i64,
+    unit: DateUnit,
+}
+
+impl RelativeSpan {
+    fn parse(value: &str) -> DbResult<Self> {
+        let invalid = || {
+            DbError::InvalidOperation(format!(
+                "'{}' is not a relative date; use a form like '7 days'",
+                value
+            ))
+        };
+        let mut parts = value.split_whitespace();
+        let (Some(amount), Some(unit), None) = (parts.next(), parts.next(), parts.next()) else {
+            return Err(invalid());
+        };
+        let amount: i64 = amount.parse().map_err(|_| invalid())?;
+        if amount < 0 {
+            return Err(invalid());
+        }
+        let unit = match unit.to_lowercase().trim_end_matches('s') {
+            "minute" | "min" => DateUnit::Minute,
+            "hour" => DateUnit::Hour,
+            "day" => DateUnit::Day,
+            "week" => DateUnit::Week,
+            "month" => DateUnit::Month,
+            "year" => DateUnit::Year,
+            _ => return Err(invalid()),
+        };
+        Ok(Self { amount, unit })
+    }
+}
+
+fn quote_ident(name: &str, db_type: DatabaseType) -> String {
+    match db_type {
+        DatabaseType::MySQL => format!("`{}`", name.replace('`', "``")),
+        DatabaseType::PostgreSQL | DatabaseType::SQLite => {
+            format!("\"{}\"", name.replace('"', "\"\""))
+        }
+    }
+}
+
+/// Makes `%`, `_` and the escape character match themselves.
+fn escape_like(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for ch in value.chars() {
+        if matches!(ch, '%' | '_') || ch == LIKE_ESCAPE {
+            escaped.push(LIKE_ESCAPE);
+        }
+        escaped.push(ch);
+    }
+    escaped
+}
+
+/// The type to cast a bound value to. Enums and arrays are reported by
+/// category rather than by name and compared as text instead.
+fn pg_cast_type(data_type: &str) -> Option<&str> {
+    match data_type {
+        "USER-DEFINED" | "ARRAY" | "" => None,
+        other => Some(other),
+    }
+}
+
+/// Converts a filter value to the kind of value the column holds, so numbers
+/// compare as numbers rather than as text.
+fn typed_value(column: &ColumnInfo, raw: &str) -> DbResult<ParamValue> {
+    let data_type = column.data_type.to_lowercase();
+    let invalid = || {
+        DbError::InvalidOperation(format!(
+            "'{}' is not a valid {} value for \"{}\"",
+            raw, column.data_type, column.name
+        ))
+    };
+
+    if data_type.contains("bool") {
+        match raw.trim().to_lowercase().as_str() {
+            "true" | "t" | "1" | "yes" => Ok(ParamValue::Bool(true)),
+            "false" | "f" | "0" | "no" => Ok(ParamValue::Bool(false)),
+            _ => Err(invalid()),
+        }
+    } else if data_type.contains("int") && !data_type.contains("point") {
+        raw.trim()
+            .parse()
+            .map(ParamValue::Int)
+            .map_err(|_| invalid())
+    } else if ["real", "float", "double"]
+        .iter()
+        .any(|t| data_type.contains(t))
+    {
+        raw.trim()
+            .parse()
+            .map(ParamValue::Float)
+            .map_err(|_| invalid())
+    } else {
+        // DECIMAL stays text so that no precision is lost on the way.
+        Ok(ParamValue::Text(raw.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use DatabaseType::*;
+
+    fn columns() -> Vec<ColumnInfo> {
+        [
+            ("id", "integer"),
+            ("name", "text"),
+            ("mood", "USER-DEFINED"),
+            ("created", "date"),
+            ("active", "boolean"),
+            ("price", "double precision"),
+            ("we\"ird`", "text"),
+            ("code", "character(10)"),
+        ]
+        .into_iter()
+        .map(|(name, data_type)| ColumnInfo {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            is_nullable: true,
+            is_primary_key: false,
+            primary_key_ordinal: None,
+            default_value: None,
+        })
+        .collect()
+    }
+
+    fn cond(column: &str, operator: &str, value: &str) -> Value {
+        json!({ "column": column, "operator": operator, "value": value })
+    }
+
+    fn text(value: &str) -> Value {
+        json!({ "type": "text", "value": value })
+    }
+
+    #[test]
+    fn compiles_filters() {
+        let cases = [
+            (json!({ "combinator": "and", "children": [] }), PostgreSQL, "", json!([])),
+            (
+                json!({ "combinator": "or", "children": [{ "combinator": "and", "children": [] }] }),
+                MySQL,
+                "",
+                json!([]),
+            ),
+            (
+                cond("id", "equals", "5"),
+                PostgreSQL,
+                "WHERE \"id\" = CAST($1 AS integer)",
+                json!([text("5")]),
+            ),
+            (
+                cond("id", "equals", "5"),
+                MySQL,
+                "WHERE `id` = ?",
+                json!([{ "type": "int", "value": 5 }]),
+            ),
+            (
+                cond("active", "notequals", "yes"),
+                SQLite,
+                "WHERE \"active\" <> ?",
+                json!([{ "type": "bool", "value": true }]),
+            ),
+            (
+                cond("price", "lessthanorequal", " 2.5 "),
+                SQLite,
+                "WHERE \"price\" <= ?",
+                json!([{ "type": "float", "value": 2.5 }]),
+            ),
+            // The cast keeps the length; a bare `character` is char(1).
+            (
+                cond("code", "equals", "AB"),
+                PostgreSQL,
+                "WHERE \"code\" = CAST($1 AS character(10))",
+                json!([text("AB")]),
+            ),
+            // Enums and other user-defined types are compared as text.
+            (
+                cond("mood", "equals", "happy"),
+                PostgreSQL,
+                "WHERE \"mood\"::text = $1",
+                json!([text("happy")]),
+            ),
+            (
+                cond("mood", "contains", "ha"),
+                PostgreSQL,
+                "WHERE \"mood\"::text ILIKE $1 ESCAPE '!'",
+                json!([text("%ha%")]),
+            ),
+            // Wildcards typed by the user match themselves.
+            (
+                cond("name", "startswith", "50%_off!"),
+                SQLite,
+                "WHERE \"name\" LIKE ? ESCAPE '!'",
+                json!([text("50!%!_off!!%")]),
+            ),
+            (
+                cond("name", "notlike", "a%"),
+                MySQL,
+                "WHERE `name` NOT LIKE ? ESCAPE '!'",
+                json!([text("a%")]),
+            ),
+            (
+                cond("we\"ird`", "isnull", ""),
+                PostgreSQL,
+                "WHERE \"we\"\"ird`\" IS NULL",
+                json!([]),
+            ),
+            (
+                cond("we\"ird`", "isnotnull", ""),
+                MySQL,
+                "WHERE `we\"ird``` IS NOT NULL",
+                json!([]),
+            ),
+            (
+                cond("name", "isempty", ""),
+                MySQL,
+                "WHERE COALESCE(CAST(`name` AS CHAR), '') = ''",
+                json!([]),
+            ),
+            (
+                cond("name", "regex", "^a"),
+                SQLite,
+                "WHERE \"name\" REGEXP ?",
+                json!([text("^a")]),
+            ),
+            (
+                json!({ "column": "id", "operator": "in", "values": ["1", "2"] }),
+                MySQL,
+                "WHERE `id` IN (?, ?)",
+                json!([{ "type": "int", "value": 1 }, { "type": "int", "value": 2 }]),
+            ),
+            (
+                json!({ "column": "id", "operator": "in", "values": [] }),
+                PostgreSQL,
+                "WHERE 1 = 0",
+                json!([]),
+            ),
+            (
+                json!({ "column": "id", "operator": "notin", "values": [] }),
+                PostgreSQL,
+                "WHERE 1 = 1",
+                json!([]),
+            ),
+            (
+                json!({ "column": "created", "operator": "between", "values": ["2024-01-01", "2024-12-31"] }),
+                PostgreSQL,
+                "WHERE \"created\" BETWEEN CAST($1 AS date) AND CAST($2 AS date)",
+                json!([text("2024-01-01"), text("2024-12-31")]),
+            ),
+            (
+                cond("created", "withinlast", "7 days"),
+                PostgreSQL,
+                "WHERE \"created\" BETWEEN CURRENT_TIMESTAMP - CAST($1 AS interval) AND CURRENT_TIMESTAMP",
+                json!([text("7 days")]),
+            ),
+            (
+                cond("created", "withinnext", "1 Month"),
+                MySQL,
+                "WHERE `created` BETWEEN NOW() AND NOW() + INTERVAL ? MONTH",
+                json!([{ "type": "int", "value": 1 }]),
+            ),
+            (
+                cond("created", "olderthan", "2 weeks"),
+                SQLite,
+                "WHERE \"created\" < datetime('now', ?)",
+                json!([text("-14 days")]),
+            ),
+            // Nested groups are parenthesised and placeholders numbered in
+            // the order their values are bound.
+            (
+                json!({
+                    "combinator": "and",
+                    "children": [
+                        cond("id", "greaterthan", "1"),
+                        {
+                            "combinator": "or",
+                            "children": [
+                                cond("name", "startswith", "a"),
+                                cond("name", "isnull", ""),
+                            ],
+                        },
+                    ],
+                }),
+                PostgreSQL,
+                "WHERE (\"id\" > CAST($1 AS integer) AND (\"name\"::text ILIKE $2 ESCAPE '!' OR \"name\" IS NULL))",
+                json!([text("1"), text("a%")]),
+            ),
+            // A group left with one condition needs no parentheses.
+            (
+                json!({
+                    "combinator": "or",
+                    "children": [
+                        { "combinator": "and", "children": [] },
+                        cond("id", "lessthan", "3"),
+                    ],
+                }),
+                SQLite,
+                "WHERE \"id\" < ?",
+                json!([{ "type": "int", "value": 3 }]),
+            ),
+            (
+                json!({
+                    "combinator": "and",
+                    "negated": true,
+                    "children": [cond("id", "greaterthanorequal", "3")],
+                }),
+                MySQL,
+                "WHERE NOT (`id` >= ?)",
+                json!([{ "type": "int", "value": 3 }]),
+            ),
+        ];
+        let columns = columns();
+        for (filter, db_type, clause, values) in cases {
+            let node: FilterNode = serde_json::from_value(filter.clone()).unwrap();
+            let (actual_clause, actual_values) = compile_filters(&node, &columns, db_type)
+                .unwrap_or_else(|e| panic!("{}: {}", filter, e));
+            assert_eq!(actual_clause, clause, "{}", filter);
+            assert_eq!(
+                serde_json::to_value(&actual_values).unwrap(),
+                values,
+                "{}",
+                filter
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_filters() {
+        let cases = [
+            (cond("missing", "equals", "1"), PostgreSQL),
+            (cond("id", "equals", "abc"), MySQL),
+            (cond("active", "equals", "maybe"), SQLite),
+            (
+                json!({ "column": "id", "operator": "between", "values": ["1"] }),
+                PostgreSQL,
+            ),
+            (cond("created", "withinlast", "soon"), PostgreSQL),
+            (cond("created", "withinlast", "-1 days"), MySQL),
+            (cond("created", "olderthan", "3 fortnights"), SQLite),
+            (
+                json!({ "combinator": "and", "children": [cond("id", "isnull", ""), cond("nope", "isnull", "")] }),
+                SQLite,
+            ),
+        ];
+        let columns = columns();
+        for (filter, db_type) in cases {
+            let node: FilterNode = serde_json::from_value(filter.clone()).unwrap();
+            assert!(
+                compile_filters(&node, &columns, db_type).is_err(),
+                "{}",
+                filter
+            );
+        }
+    }
+}
@@ -1,8 +1,23 @@
+pub mod cancel;
+pub mod classify;
+pub mod explain;
 pub mod factory;
+pub mod filter;
 pub mod mysql;
+pub mod notices;
+pub mod params;
 pub mod postgres;
+pub mod script;
+pub mod session;
 pub mod sqlite;
 pub mod traits;
 
+pub use classify::{
+    accepts_returning, allowed_read_only, classify, find_destructive, Destructive, DestructiveKind,
+    StatementKind, TableRef,
+};
+pub use explain::QueryPlan;
 pub use factory::ConnectionFactory;
+pub use params::resolve_params;
+pub use script::{split_statements, StatementResult};
 pub use traits::*;
@@ -1,31 +1,92 @@
+use super::cancel::RunningQueries;
+use super::classify::{classify, written_tables, StatementKind};
+use super::explain::{self, QueryPlan};
+use super::filter::compile_filters;
+use super::notices::append_messages;
+use super::params::json_param;
+use super::script::{ScriptStatement, StatementResult};
+use super::session::SessionPool;
 use super::traits::*;
 use async_trait::async_trait;
-use sqlx::mysql::{MySqlConnectOptions, MySqlPool, MySqlPoolOptions, MySqlRow};
-use sqlx::{Column, Row};
-use std::str::FromStr;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
+use futures::TryStreamExt;
+use sqlx::mysql::types::MySqlTime;
+use sqlx::mysql::{MySqlConnectOptions, MySqlPoolOptions, MySqlRow, MySqlSslMode};
+use sqlx::types::BigDecimal;
+use sqlx::{Column, Executor, MySql, Row, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::time::Instant;
 
 pub struct MySqlConnection {
-    pool: MySqlPool,
-    in_transaction: AtomicBool,
+    running: RunningQueries<u64>,
+    session: SessionPool<MySql>,
 }
 
 impl MySqlConnection {
-    pub async fn new(connection_string: &str) -> DbResult<Self> {
-        let options = MySqlConnectOptions::from_str(connection_string)
-            .map_err(|e| DbError::Connection(e.to_string()))?;
+    pub async fn new(params: &ConnectionParams) -> DbResult<Self> {
+        let tls = &params.tls;
+        let mut options = MySqlConnectOptions::new()
+            .host(&params.host)
+            .port(params.port)
+            .database(&params.database)
+            .username(&params.username)
+            .password(&params.password)
+            .ssl_mode(match tls.ssl_mode {
+                SslMode::Disable => MySqlSslMode::Disabled,
+                SslMode::Prefer => MySqlSslMode::Preferred,
+                SslMode::Require => MySqlSslMode::Required,
+                SslMode::VerifyCa => MySqlSslMode::VerifyCa,
+                SslMode::VerifyFull => MySqlSslMode::VerifyIdentity,
+            });
+        if let Some(path) = &params.socket {
+            options = options.socket(path);
+        }
+        if let Some(path) = &tls.ca_cert_path {
+            options = options.ssl_ca(path);
+        }
+        if let Some(path) = &tls.client_cert_path {
+            options = options.ssl_client_cert(path);
+        }
+        if let Some(path) = &tls.client_key_path {
+            options = options.ssl_client_key(path);
+        }
+        for (key, value) in &params.parameters {
+            options = match key.as_str() {
+                "connect_timeout" => options,
+                // MySQL would take this as the program_name connection
+                // attribute, which sqlx has no way to send. It is ignored so
+                // that settings shared with PostgreSQL still connect.
+                "application_name" => options,
+                "charset" => options.charset(value),
+                "collation" => options.collation(value),
+                "timezone" => options.timezone(value.clone()),
+                _ => {
+                    return Err(DbError::Connection(format!(
+                        "Unsupported MySQL parameter: {}",
+                        key
+                    )))
+                }
+            };
+        }
 
+        let read_only = params.read_only;
         let pool = MySqlPoolOptions::new()
             .max_connections(10)
-            .acquire_timeout(Duration::from_secs(10))
+            .acquire_timeout(params.connect_timeout()?)
+            .after_connect(move |conn, _meta| {
+                Box::pin(async move {
+                    if read_only {
+                        conn.execute("SET SESSION TRANSACTION READ ONLY").await?;
+                    }
+                    Ok(())
+                })
+            })
             .connect_with(options)
             .await
             .map_err(|e| DbError::Connection(e.to_string()))?;
 
         Ok(Self {
-            pool,
-            in_transaction: AtomicBool::new(false),
+            running: RunningQueries::default(),
+            session: SessionPool::new(pool, read_only),
         })
     }
 
@@ -53,45 +114,46 @@ impl MySqlConnection {
         }
     }
 
-    fn build_where_clause(&self, filters: &Option<Vec<FilterCondition>>) -> String {
-        let Some(filters) = filters else {
-            return String::new();
-        };
-        if filters.is_empty() {
-            return String::new();
+    /// Matches one row by every column of its primary key. Key values are
+    /// bound after any already in `values`.
+    fn build_key_clause(
+        key: &HashMap<String, serde_json::Value>,
+        values: &mut Vec<ParamValue>,
+    ) -> DbResult<String> {
+        if key.is_empty() {
+            return Err(DbError::InvalidOperation(
+                "Rows can only be changed in tables with a primary key".to_string(),
+            ));
         }
-
-        let conditions: Vec<String> = filters
-            .iter()
-            .map(|f| match f.operator {
-                FilterOperator::Equals => {
-                    format!("`{}` = '{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::NotEquals => {
-                    format!("`{}` != '{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::Contains => {
-                    format!("`{}` LIKE '%{}%'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::StartsWith => {
-                    format!("`{}` LIKE '{}%'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::EndsWith => {
-                    format!("`{}` LIKE '%{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::GreaterThan => {
-                    format!("`{}` > '{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::LessThan => {
-                    format!("`{}` < '{}'", f.column, f.value.replace('\'', "''"))
+        let mut columns: Vec<_> = key.iter().collect();
+        columns.sort_by(|a, b| a.0.cmp(b.0));
+        let conditions: Vec<String> = columns
+            .into_iter()
+            .map(|(col, val)| {
+                if val.is_null() {
+                    format!("{} IS NULL", Self::quote_column(col))
+                } else {
+                    values.push(json_param(val));
+                    format!("{} = ?", Self::quote_column(col))
                 }
-                FilterOperator::IsNull => format!("`{}` IS NULL", f.column),
-                FilterOperator::IsNotNull => format!("`{}` IS NOT NULL", f.column),
-                FilterOperator::Raw => format!("`{}` {}", f.column, f.value),
             })
             .collect();
+        Ok(conditions.join(" AND "))
+    }
 
-        format!("WHERE {}", conditions.join(" AND "))
+    fn quote_column(col: &str) -> String {
+        format!("`{}`", col.replace('`', "``"))
+    }
+
+    fn build_where_clause(
+        &self,
+        filters: &Option<FilterNode>,
+        columns: &[ColumnInfo],
+    ) -> DbResult<(String, Vec<ParamValue>)> {
+        match filters {
+            Some(filters) => compile_filters(filters, columns, DatabaseType::MySQL),
+            None => Ok((String::new(), Vec::new())),
+        }
     }
 
     fn build_order_clause(&self, sort: &Option<Vec<SortColumn>>) -> String {
@@ -115,6 +177,395 @@ impl MySqlConnection {
 
         format!("ORDER BY {}", orders.join(", "))
     }
+
+    fn row_to_json(row: &MySqlRow) -> Vec<serde_json::Value> {
+        (0..row.len())
+            .map(|index| Self::decode_value(row, index))
+            .collect()
+    }
+
+    /// Decodes a result cell according to its column's type rather than by
+    /// trial and error, so ad-hoc results keep decimals, temporal values and
+    /// binary data. Unknown types fall back to their text form.
+    fn decode_value(row: &MySqlRow, index: usize) -> serde_json::Value {
+        use serde_json::Value;
+        match row.try_get_raw(index) {
+            Ok(raw) if !raw.is_null() => {}
+            _ => return Value::Null,
+        }
+        let type_name = row.columns()[index].type_info().name();
+        if let Some(base) = type_name.strip_suffix(" UNSIGNED") {
+            return match base {
+                "DECIMAL" => Self::decode_decimal(row, index),
+                "FLOAT" => Self::decode::<f32>(row, index, |v| Value::from(v as f64)),
+                "DOUBLE" => Self::decode::<f64>(row, index, Value::from),
+                _ => Self::decode::<u64>(row, index, Value::from),
+            };
+        }
+
+        match type_name {
+            "BOOLEAN" => Self::decode::<bool>(row, index, Value::from),
+            "TINYINT" | "SMALLINT" | "MEDIUMINT" | "INT" | "BIGINT" => {
+                Self::decode::<i64>(row, index, Value::from)
+            }
+            // YEAR and BIT have no checked Rust counterpart, but decode as
+            // plain integers.
+            "YEAR" | "BIT" => row
+                .try_get_unchecked::<u64, _>(index)
+                .map(Value::from)
+                .unwrap_or(Value::Null),
+            "FLOAT" => Self::decode::<f32>(row, index, |v| Value::from(v as f64)),
+            "DOUBLE" => Self::decode::<f64>(row, index, Value::from),
+            "DECIMAL" => Self::decode_decimal(row, index),
+            "JSON" => Self::decode::<Value>(row, index, |v| v),
+            "DATE" => {
+                Self::decode::<chrono::NaiveDate>(row, index, |v| Value::String(v.to_string()))
+            }
+            "TIME" => Self::decode::<MySqlTime>(row, index, |v| Value::String(v.to_string())),
+            "DATETIME" => {
+                Self::decode::<chrono::NaiveDateTime>(row, index, |v| Value::String(v.to_string()))
+            }
+            "TIMESTAMP" => Self::decode::<chrono::DateTime<chrono::Utc>>(row, index, |v| {
+                Value::String(v.to_rfc3339())
+            }),
+            "BINARY" | "VARBINARY" | "TINYBLOB" | "BLOB" | "MEDIUMBLOB" | "LONGBLOB"
+            | "GEOMETRY" => row
+                .try_get_unchecked::<Vec<u8>, _>(index)
+                .map(|v| Value::String(Self::format_binary(&v)))
+                .unwrap_or(Value::Null),
+            _ => row
+                .try_get_unchecked::<String, _>(index)
+                .map(Value::from)
+                .unwrap_or(Value::Null),
+        }
+    }
+
+    fn decode<'r, T>(
+        row: &'r MySqlRow,
+        index: usize,
+        to_json: impl FnOnce(T) -> serde_json::Value,
+    ) -> serde_json::Value
+    where
+        T: sqlx::Decode<'r, MySql> + sqlx::Type<MySql>,
+    {
+        row.try_get::<T, _>(index)
+            .map(to_json)
+            .unwrap_or(serde_json::Value::Null)
+    }
+
+    /// Decimals are returned as strings so no precision is lost in JSON.
+    fn decode_decimal(row: &MySqlRow, index: usize) -> serde_json::Value {
+        Self::decode::<BigDecimal>(row, index, |v| serde_json::Value::String(v.to_string()))
+    }
+
+    /// Formats bytes as a MySQL hexadecimal literal.
+    fn format_binary(bytes: &[u8]) -> String {
+        let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        format!("0x{}", hex)
+    }
+
+    /// Column names, engine types and nullability as reported by the
+    /// prepared statement, so they are known even when no rows come back.
+    async fn describe_columns(
+        conn: &mut sqlx::MySqlConnection,
+        sql: &str,
+    ) -> Option<Vec<ResultColumn>> {
+        let desc = (&mut *conn).describe(sql).await.ok()?;
+        let columns = desc
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, col)| ResultColumn::new(col.name(), col.type_info().name(), desc.nullable(i)))
+            .collect();
+        Some(columns)
+    }
+
+    fn row_columns(row: &MySqlRow) -> Vec<ResultColumn> {
+        row.columns()
+            .iter()
+            .map(|col| ResultColumn::new(col.name(), col.type_info().name(), None))
+            .collect()
+    }
+
+    /// Reads the warnings the previous statement left, if it left any. The
+    /// count is checked first since `SHOW WARNINGS` is a round trip of its own.
+    async fn warnings(conn: &mut sqlx::MySqlConnection) -> Vec<QueryMessage> {
+        let count = (&mut *conn)
+            .fetch_one("SELECT @@warning_count")
+            .await
+            .and_then(|row| row.try_get_unchecked::<u64, _>(0));
+        if !matches!(count, Ok(count) if count > 0) {
+            return Vec::new();
+        }
+
+        let rows = (&mut *conn)
+            .fetch_all("SHOW WARNINGS")
+            .await
+            .unwrap_or_default();
+        rows.iter()
+            .map(|row| {
+                let level: String = row.try_get("Level").unwrap_or_default();
+                QueryMessage {
+                    severity: match level.as_str() {
+                        "Error" => MessageSeverity::Error,
+                        "Warning" => MessageSeverity::Warning,
+                        _ => MessageSeverity::Notice,
+                    },
+                    code: row
+                        .try_get_unchecked::<u32, _>("Code")
+                        .ok()
+                        .map(|code| code.to_string()),
+                    text: row.try_get("Message").unwrap_or_default(),
+                }
+            })
+            .collect()
+    }
+
+    async fn track_query(&self, conn: &mut sqlx::MySqlConnection, query_id: &str) -> DbResult<()> {
+        let connection_id: u64 = sqlx::query_scalar("SELECT CONNECTION_ID()")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        self.running.register(query_id, connection_id);
+        Ok(())
+    }
+
+    async fn stream_rows(
+        &self,
+        conn: &mut sqlx::MySqlConnection,
+        sql: &str,
+        batch_size: usize,
+        on_event: &mut (dyn FnMut(QueryEvent) + Send),
+    ) -> DbResult<()> {
+        let start = Instant::now();
+        let columns = Self::describe_columns(conn, sql).await.unwrap_or_default();
+        on_event(QueryEvent::Columns { columns });
+
+        let mut rows = (&mut *conn).fetch(sql);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut row_count = 0u64;
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?
+        {
+            batch.push(Self::row_to_json(&row));
+            row_count += 1;
+            if batch.len() >= batch_size {
+                on_event(QueryEvent::Rows {
+                    rows: std::mem::replace(&mut batch, Vec::with_capacity(batch_size)),
+                });
+            }
+        }
+        if !batch.is_empty() {
+            on_event(QueryEvent::Rows { rows: batch });
+        }
+
+        on_event(QueryEvent::Done {
+            row_count,
+            execution_time_ms: start.elapsed().as_millis(),
+        });
+        Ok(())
+    }
+
+    fn bind_params<'q>(
+        mut query: sqlx::query::Query<'q, MySql, sqlx::mysql::MySqlArguments>,
+        params: &'q [ParamValue],
+    ) -> sqlx::query::Query<'q, MySql, sqlx::mysql::MySqlArguments> {
+        for param in params {
+            query = match param {
+                ParamValue::Null => query.bind(None::<String>),
+                ParamValue::Bool(v) => query.bind(*v),
+                ParamValue::Int(v) => query.bind(*v),
+                ParamValue::Float(v) => query.bind(*v),
+                ParamValue::Text(v) => query.bind(v.as_str()),
+                ParamValue::Json(v) => query.bind(sqlx::types::Json(v)),
+            };
+        }
+        query
+    }
+
+    /// Refuses a dry run that writes to a table whose storage engine, such as
+    /// MyISAM, cannot roll the change back.
+    async fn ensure_transactional(conn: &mut sqlx::MySqlConnection, sql: &str) -> DbResult<()> {
+        let tables = written_tables(sql, DatabaseType::MySQL).ok_or_else(|| {
+            DbError::InvalidOperation(
+                "Cannot tell which tables the statement writes to".to_string(),
+            )
+        })?;
+        for table in tables {
+            let engine: Option<String> = sqlx::query_scalar(
+                r#"
+                SELECT t.ENGINE
+                FROM information_schema.TABLES t
+                JOIN information_schema.ENGINES e ON e.ENGINE = t.ENGINE
+                WHERE t.TABLE_SCHEMA = COALESCE(?, DATABASE())
+                    AND t.TABLE_NAME = ?
+                    AND e.TRANSACTIONS <> 'YES'
+                "#,
+            )
+            .bind(&table.schema)
+            .bind(&table.name)
+            .fetch_optional(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+            if let Some(engine) = engine {
+                return Err(DbError::InvalidOperation(format!(
+                    "Cannot dry run: `{}` uses the non-transactional {} engine, so the change would not be rolled back",
+                    table.name, engine
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Runs one statement and attaches the warnings it raised. When it fails
+    /// they are appended to the error instead, leaving out the error itself,
+    /// which MySQL also lists among them.
+    async fn run_query(
+        &self,
+        conn: &mut sqlx::MySqlConnection,
+        sql: &str,
+        params: &[ParamValue],
+        max_rows: Option<u64>,
+    ) -> DbResult<QueryResult> {
+        match self.run_statement(conn, sql, params, max_rows).await {
+            Ok(mut result) => {
+                result.messages = Self::warnings(conn).await;
+                Ok(result)
+            }
+            Err(DbError::Query(error)) => {
+                let warnings: Vec<QueryMessage> = Self::warnings(conn)
+                    .await
+                    .into_iter()
+                    .filter(|warning| !error.contains(&warning.text))
+                    .collect();
+                if warnings.is_empty() {
+                    Err(DbError::Query(error))
+                } else {
+                    Err(DbError::Query(append_messages(error, &warnings)))
+                }
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Without parameters the statement goes over the text protocol, since
+    /// CREATE PROCEDURE and similar statements cannot be prepared.
+    async fn run_statement(
+        &self,
+        conn: &mut sqlx::MySqlConnection,
+        sql: &str,
+        params: &[ParamValue],
+        max_rows: Option<u64>,
+    ) -> DbResult<QueryResult> {
+        let start = Instant::now();
+        let info = classify(sql, DatabaseType::MySQL);
+        self.session.ensure_allowed(sql, DatabaseType::MySQL)?;
+
+        if info.returns_rows {
+            let described = Self::describe_columns(conn, sql).await;
+
+            // A read stops one row past the cap and drops the rest unread. A
+            // write runs to completion anyway, so its rows are still counted.
+            let count_all = info.kind.modifies_data();
+            let mut rows = Vec::new();
+            let mut row_count = 0u64;
+            let mut truncated = false;
+            let mut stream = if params.is_empty() {
+                (&mut *conn).fetch(sql)
+            } else {
+                Self::bind_params(sqlx::query(sql), params).fetch(&mut *conn)
+            };
+            while let Some(row) = stream
+                .try_next()
+                .await
+                .map_err(|e| DbError::Query(e.to_string()))?
+            {
+                if max_rows.is_some_and(|max| rows.len() as u64 >= max) {
+                    truncated = true;
+                    if !count_all {
+                        break;
+                    }
+                } else {
+                    rows.push(row);
+                }
+                row_count += 1;
+            }
+            drop(stream);
+
+            let execution_time_ms = start.elapsed().as_millis();
+
+            let columns = described
+                .or_else(|| rows.first().map(Self::row_columns))
+                .unwrap_or_default();
+
+            let result_rows: Vec<Vec<serde_json::Value>> =
+                rows.iter().map(Self::row_to_json).collect();
+
+            Ok(QueryResult {
+                columns,
+                rows: result_rows,
+                rows_affected: row_count,
+                execution_time_ms,
+                truncated,
+                messages: Vec::new(),
+            })
+        } else {
+            let result = if params.is_empty() {
+                (&mut *conn).execute(sql).await
+            } else {
+                Self::bind_params(sqlx::query(sql), params)
+                    .execute(&mut *conn)
+                    .await
+            }
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+            Ok(QueryResult {
+                columns: vec![],
+                rows: vec![],
+                rows_affected: result.rows_affected(),
+                execution_time_ms: start.elapsed().as_millis(),
+                truncated: false,
+                messages: Vec::new(),
+            })
+        }
+    }
+
+    /// Runs an ad-hoc statement under `limits`. MySQL only applies
+    /// `max_execution_time` to read-only SELECTs, so other statements run
+    /// without a server-side timeout. The session value is put back afterwards.
+    async fn run_limited(
+        &self,
+        conn: &mut sqlx::MySqlConnection,
+        sql: &str,
+        params: &[ParamValue],
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let timeout = limits
+            .timeout_ms
+            .filter(|_| classify(sql, DatabaseType::MySQL).kind == StatementKind::Select);
+        let Some(timeout) = timeout else {
+            return self.run_query(conn, sql, params, limits.max_rows).await;
+        };
+
+        let previous: u64 = sqlx::query_scalar("SELECT @@SESSION.max_execution_time")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        (&mut *conn)
+            .execute(format!("SET SESSION max_execution_time = {}", timeout).as_str())
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        let result = self.run_query(conn, sql, params, limits.max_rows).await;
+        let reset = (&mut *conn)
+            .execute(format!("SET SESSION max_execution_time = {}", previous).as_str())
+            .await;
+        // The statement's own error wins over one from putting the value back.
+        let result = result?;
+        reset.map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(result)
+    }
 }
 
 #[async_trait]
@@ -124,14 +575,16 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn test_connection(&self) -> DbResult<()> {
+        let mut conn = self.session.acquire().await?;
         sqlx::query("SELECT 1")
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| DbError::Connection(e.to_string()))?;
         Ok(())
     }
 
     async fn get_schemas(&self) -> DbResult<Vec<SchemaInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT SCHEMA_NAME as schema_name
@@ -140,7 +593,7 @@ impl DbConnection for MySqlConnection {
             ORDER BY SCHEMA_NAME
             "#,
         )
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -153,6 +606,7 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn get_tables(&self, schema: &str) -> DbResult<Vec<TableInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT TABLE_SCHEMA as table_schema, TABLE_NAME as table_name, TABLE_TYPE as table_type
@@ -162,7 +616,7 @@ impl DbConnection for MySqlConnection {
             "#,
         )
         .bind(schema)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -177,6 +631,7 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn get_columns(&self, schema: &str, table: &str) -> DbResult<Vec<ColumnInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT 
@@ -184,15 +639,23 @@ impl DbConnection for MySqlConnection {
                 DATA_TYPE as data_type,
                 IS_NULLABLE as is_nullable,
                 COLUMN_DEFAULT as column_default,
-                COLUMN_KEY as column_key
-            FROM information_schema.COLUMNS
+                COLUMN_KEY as column_key,
+                (
+                    SELECT CAST(k.ORDINAL_POSITION AS UNSIGNED)
+                    FROM information_schema.KEY_COLUMN_USAGE k
+                    WHERE k.TABLE_SCHEMA = c.TABLE_SCHEMA
+                        AND k.TABLE_NAME = c.TABLE_NAME
+                        AND k.COLUMN_NAME = c.COLUMN_NAME
+                        AND k.CONSTRAINT_NAME = 'PRIMARY'
+                ) as key_ordinal
+            FROM information_schema.COLUMNS c
             WHERE TABLE_SCHEMA = ? AND TABLE_NAME = ?
             ORDER BY ORDINAL_POSITION
             "#,
         )
         .bind(schema)
         .bind(table)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -206,6 +669,7 @@ impl DbConnection for MySqlConnection {
                     data_type: row.get("data_type"),
                     is_nullable: nullable == "YES",
                     is_primary_key: column_key == "PRI",
+                    primary_key_ordinal: row.get::<Option<u64>, _>("key_ordinal").map(|n| n as u32),
                     default_value: row.get("column_default"),
                 }
             })
@@ -213,9 +677,10 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn get_indexes(&self, schema: &str, table: &str) -> DbResult<Vec<IndexInfo>> {
+        let mut conn = self.session.acquire().await?;
         let query = format!("SHOW INDEX FROM `{}`.`{}`", schema, table);
         let rows = sqlx::query(&query)
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -244,16 +709,17 @@ impl DbConnection for MySqlConnection {
 
     async fn get_table_data(&self, params: FetchDataParams) -> DbResult<TableData> {
         let columns = self.get_columns(&params.schema, &params.table).await?;
+        let mut conn = self.session.acquire().await?;
 
-        let where_clause = self.build_where_clause(&params.filters);
+        let (where_clause, filter_values) = self.build_where_clause(&params.filters, &columns)?;
         let order_clause = self.build_order_clause(&params.sort);
 
         let count_query = format!(
             "SELECT COUNT(*) as count FROM `{}`.`{}` {}",
             params.schema, params.table, where_clause
         );
-        let count_row = sqlx::query(&count_query)
-            .fetch_one(&self.pool)
+        let count_row = Self::bind_params(sqlx::query(&count_query), &filter_values)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         let total_count: i64 = count_row.get("count");
@@ -262,8 +728,8 @@ impl DbConnection for MySqlConnection {
             "SELECT * FROM `{}`.`{}` {} {} LIMIT {} OFFSET {}",
             params.schema, params.table, where_clause, order_clause, params.limit, params.offset
         );
-        let rows = sqlx::query(&data_query)
-            .fetch_all(&self.pool)
+        let rows = Self::bind_params(sqlx::query(&data_query), &filter_values)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -284,75 +750,114 @@ impl DbConnection for MySqlConnection {
         })
     }
 
-    async fn execute_query(&self, sql: &str) -> DbResult<QueryResult> {
-        let start = Instant::now();
-        let sql_lower = sql.trim().to_lowercase();
-        let is_select = sql_lower.starts_with("select") || sql_lower.starts_with("with");
-
-        if is_select {
-            let rows = sqlx::query(sql)
-                .fetch_all(&self.pool)
-                .await
-                .map_err(|e| DbError::Query(e.to_string()))?;
-
-            let execution_time_ms = start.elapsed().as_millis();
+    async fn execute_query(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        query_id: Option<&str>,
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let mut conn = self.session.acquire().await?;
+        let Some(query_id) = query_id else {
+            return self.run_limited(&mut conn, sql, params, limits).await;
+        };
 
-            if rows.is_empty() {
-                return Ok(QueryResult {
-                    columns: vec![],
-                    rows: vec![],
-                    rows_affected: 0,
-                    execution_time_ms,
-                });
-            }
+        self.track_query(&mut conn, query_id).await?;
+        let result = self.run_limited(&mut conn, sql, params, limits).await;
+        self.running.finish(query_id, result).await
+    }
 
-            let columns: Vec<String> = rows[0]
-                .columns()
-                .iter()
-                .map(|c| c.name().to_string())
-                .collect();
-
-            let mut result_rows: Vec<Vec<serde_json::Value>> = Vec::new();
-            for row in &rows {
-                let mut row_data: Vec<serde_json::Value> = Vec::new();
-                for col in row.columns() {
-                    let value: serde_json::Value = row
-                        .try_get::<String, _>(col.name())
-                        .map(serde_json::Value::from)
-                        .or_else(|_| {
-                            row.try_get::<i64, _>(col.name())
-                                .map(serde_json::Value::from)
-                        })
-                        .or_else(|_| {
-                            row.try_get::<f64, _>(col.name())
-                                .map(serde_json::Value::from)
-                        })
-                        .unwrap_or(serde_json::Value::Null);
-                    row_data.push(value);
-                }
-                result_rows.push(row_data);
+    async fn execute_script(
+        &self,
+        statements: &[ScriptStatement],
+        stop_on_error: bool,
+    ) -> DbResult<Vec<StatementResult>> {
+        // Every statement runs on the same session so that SET, temporary
+        // tables and explicit BEGIN/COMMIT carry over between them.
+        let mut conn = self.session.acquire().await?;
+        let mut results = Vec::with_capacity(statements.len());
+        for statement in statements {
+            let outcome = self.run_query(&mut conn, &statement.sql, &[], None).await;
+            let failed = outcome.is_err();
+            results.push(StatementResult::new(statement, outcome));
+            if failed && stop_on_error {
+                break;
             }
+        }
+        conn.rollback_unpinned().await;
+        Ok(results)
+    }
 
-            let rows_affected = result_rows.len() as u64;
-            Ok(QueryResult {
-                columns,
-                rows: result_rows,
-                rows_affected,
-                execution_time_ms,
-            })
+    async fn explain_query(&self, sql: &str, analyze: bool) -> DbResult<QueryPlan> {
+        let sql = sql.trim().trim_end_matches(';');
+        // ANALYZE really runs the statement, so writes are rolled back.
+        let discard = analyze && classify(sql, DatabaseType::MySQL).kind.modifies_data();
+        if discard {
+            self.session.ensure_writable()?;
+        }
+        let explain = if analyze {
+            format!("EXPLAIN ANALYZE {}", sql)
         } else {
-            let result = sqlx::query(sql)
-                .execute(&self.pool)
-                .await
-                .map_err(|e| DbError::Query(e.to_string()))?;
+            format!("EXPLAIN FORMAT=JSON {}", sql)
+        };
 
-            Ok(QueryResult {
-                columns: vec![],
-                rows: vec![],
-                rows_affected: result.rows_affected(),
-                execution_time_ms: start.elapsed().as_millis(),
-            })
+        let mut conn = self.session.acquire().await?;
+        if discard {
+            conn.begin_scratch().await?;
         }
+        let output = (&mut *conn)
+            .fetch_one(explain.as_str())
+            .await
+            .and_then(|row| row.try_get::<String, _>(0))
+            .map_err(|e| DbError::Query(e.to_string()));
+        if discard {
+            conn.discard_scratch().await?;
+        }
+
+        let output = output?;
+        if analyze {
+            explain::parse_mysql_tree(&output)
+        } else {
+            explain::parse_mysql_json(&output)
+        }
+    }
+
+    async fn dry_run(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let mut conn = self.session.acquire_writable().await?;
+        Self::ensure_transactional(&mut conn, sql).await?;
+        conn.begin_scratch().await?;
+        let outcome = self.run_limited(&mut conn, sql, params, limits).await;
+        conn.discard_scratch().await?;
+        outcome
+    }
+
+    async fn stream_query(
+        &self,
+        sql: &str,
+        query_id: &str,
+        batch_size: usize,
+        on_event: &mut (dyn FnMut(QueryEvent) + Send),
+    ) -> DbResult<()> {
+        self.session.ensure_allowed(sql, DatabaseType::MySQL)?;
+        let mut conn = self.session.acquire().await?;
+        self.track_query(&mut conn, query_id).await?;
+        let result = self.stream_rows(&mut conn, sql, batch_size, on_event).await;
+        self.running.finish(query_id, result).await
+    }
+
+    async fn cancel_query(&self, query_id: &str) -> DbResult<()> {
+        let mut conn = self.session.acquire_unpinned().await?;
+        let (connection_id, _pending) = self.running.cancel(query_id, |id| *id)?;
+        sqlx::query(&format!("KILL QUERY {}", connection_id))
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(())
     }
 
     async fn get_distinct_values(
@@ -362,6 +867,7 @@ impl DbConnection for MySqlConnection {
         column: &str,
         limit: Option<u32>,
     ) -> DbResult<Vec<serde_json::Value>> {
+        let mut conn = self.session.acquire().await?;
         let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
         let sql = format!(
             "SELECT DISTINCT `{}` FROM `{}`.`{}` WHERE `{}` IS NOT NULL ORDER BY `{}`{}",
@@ -369,7 +875,7 @@ impl DbConnection for MySqlConnection {
         );
 
         let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -384,46 +890,49 @@ impl DbConnection for MySqlConnection {
         Ok(values)
     }
 
+    async fn estimate_row_count(&self, schema: Option<&str>, table: &str) -> DbResult<Option<u64>> {
+        let mut conn = self.session.acquire().await?;
+        let row = sqlx::query(
+            r#"
+            SELECT TABLE_ROWS
+            FROM information_schema.TABLES
+            WHERE TABLE_SCHEMA = COALESCE(?, DATABASE()) AND TABLE_NAME = ?
+            "#,
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+
+        Ok(row.and_then(|row| row.try_get_unchecked::<Option<u64>, _>(0).ok().flatten()))
+    }
+
     async fn update_row(&self, update: RowUpdate) -> DbResult<u64> {
-        let format_value = |v: &serde_json::Value| -> String {
-            if v.is_null() {
-                "NULL".to_string()
-            } else if v.is_number() {
-                v.to_string()
-            } else if v.is_boolean() {
-                if v.as_bool().unwrap() {
-                    "1".to_string()
-                } else {
-                    "0".to_string()
-                }
-            } else if v.is_string() {
-                let s = v.as_str().unwrap();
-                format!("'{}'", s.replace('\'', "''"))
-            } else {
-                let s = v.to_string();
-                format!("'{}'", s.replace('\'', "''"))
-            }
-        };
+        let mut conn = self.session.acquire_writable().await?;
 
+        let mut values = Vec::new();
         let set_clauses: Vec<String> = update
             .updates
             .iter()
-            .map(|(col, val)| format!("`{}` = {}", col, format_value(val)))
+            .map(|(col, val)| {
+                values.push(json_param(val));
+                format!("{} = ?", Self::quote_column(col))
+            })
             .collect();
 
-        let pk_formatted = format_value(&update.primary_key_value);
+        let key_clause = Self::build_key_clause(&update.primary_key, &mut values)?;
 
         let sql = format!(
-            "UPDATE `{}`.`{}` SET {} WHERE `{}` = {}",
+            "UPDATE `{}`.`{}` SET {} WHERE {}",
             update.schema,
             update.table,
             set_clauses.join(", "),
-            update.primary_key_column,
-            pk_formatted
+            key_clause
         );
 
-        let result = sqlx::query(&sql)
-            .execute(&self.pool)
+        let result = Self::bind_params(sqlx::query(&sql), &values)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -431,6 +940,7 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn insert_row(&self, insert: RowInsert) -> DbResult<serde_json::Value> {
+        let mut conn = self.session.acquire_writable().await?;
         let columns: Vec<String> = insert.values.keys().map(|k| format!("`{}`", k)).collect();
 
         let values: Vec<String> = insert
@@ -466,7 +976,7 @@ impl DbConnection for MySqlConnection {
         );
 
         let result = sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -474,25 +984,17 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn delete_row(&self, delete: RowDelete) -> DbResult<u64> {
-        let pk_formatted = if delete.primary_key_value.is_null() {
-            "NULL".to_string()
-        } else if delete.primary_key_value.is_number() {
-            delete.primary_key_value.to_string()
-        } else if delete.primary_key_value.is_string() {
-            let s = delete.primary_key_value.as_str().unwrap();
-            format!("'{}'", s.replace('\'', "''"))
-        } else {
-            let s = delete.primary_key_value.to_string();
-            format!("'{}'", s.replace('\'', "''"))
-        };
+        let mut conn = self.session.acquire_writable().await?;
+        let mut values = Vec::new();
+        let key_clause = Self::build_key_clause(&delete.primary_key, &mut values)?;
 
         let sql = format!(
-            "DELETE FROM `{}`.`{}` WHERE `{}` = {}",
-            delete.schema, delete.table, delete.primary_key_column, pk_formatted
+            "DELETE FROM `{}`.`{}` WHERE {}",
+            delete.schema, delete.table, key_clause
         );
 
-        let result = sqlx::query(&sql)
-            .execute(&self.pool)
+        let result = Self::bind_params(sqlx::query(&sql), &values)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -500,33 +1002,37 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn create_schema(&self, name: &str) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let sql = format!("CREATE DATABASE `{}`", name);
         sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         Ok(())
     }
 
     async fn drop_schema(&self, name: &str, _cascade: bool) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let sql = format!("DROP DATABASE `{}`", name);
         sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         Ok(())
     }
 
     async fn drop_table(&self, schema: &str, table: &str, _cascade: bool) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let sql = format!("DROP TABLE `{}`.`{}`", schema, table);
         sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         Ok(())
     }
 
     async fn alter_table(&self, params: AlterTableParams) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let table_name = format!("`{}`.`{}`", params.schema, params.table);
 
         for change in params.changes {
@@ -567,7 +1073,7 @@ impl DbConnection for MySqlConnection {
             };
 
             sqlx::query(&sql)
-                .execute(&self.pool)
+                .execute(&mut *conn)
                 .await
                 .map_err(|e| DbError::Query(e.to_string()))?;
         }
@@ -576,38 +1082,63 @@ impl DbConnection for MySqlConnection {
     }
 
     async fn begin_transaction(&self) -> DbResult<()> {
-        sqlx::query("START TRANSACTION")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(true, Ordering::SeqCst);
-        Ok(())
+        self.session.begin("START TRANSACTION").await
     }
 
     async fn commit(&self) -> DbResult<()> {
-        sqlx::query("COMMIT")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(false, Ordering::SeqCst);
-        Ok(())
+        self.session.end("COMMIT").await
     }
 
     async fn rollback(&self) -> DbResult<()> {
-        sqlx::query("ROLLBACK")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(false, Ordering::SeqCst);
-        Ok(())
+        self.session.end("ROLLBACK").await
     }
 
     async fn in_transaction(&self) -> bool {
-        self.in_transaction.load(Ordering::SeqCst)
+        self.session.in_transaction().await
     }
 
     async fn close(&self) -> DbResult<()> {
-        self.pool.close().await;
+        self.session.close().await;
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::script::split_statements;
+
+    /// A scratch database named by the `MYSQL_TEST_*` variables. Tests that
+    /// need a server return early when `MYSQL_TEST_HOST` is not set.
+    async fn connect() -> Option<MySqlConnection> {
+        let var = |name: &str| std::env::var(name).unwrap_or_default();
+        let params = ConnectionParams {
+            host: std::env::var("MYSQL_TEST_HOST").ok()?,
+            port: var("MYSQL_TEST_PORT").parse().unwrap_or(3306),
+            database: var("MYSQL_TEST_DATABASE"),
+            username: var("MYSQL_TEST_USER"),
+            password: var("MYSQL_TEST_PASSWORD"),
+            ..Default::default()
+        };
+        Some(MySqlConnection::new(&params).await.unwrap())
+    }
+
+    #[tokio::test]
+    async fn runs_procedure_bodies() {
+        let Some(db) = connect().await else {
+            return;
+        };
+        let script = "DROP PROCEDURE IF EXISTS script_test;\n\
+            DELIMITER //\n\
+            CREATE PROCEDURE script_test() BEGIN\n  SELECT 1;\nEND //\n\
+            DELIMITER ;\n\
+            CALL script_test();\n\
+            DROP PROCEDURE script_test;";
+        let statements = split_statements(script, DatabaseType::MySQL);
+        let results = db.execute_script(&statements, true).await.unwrap();
+        assert_eq!(results.len(), 4);
+        for result in results {
+            assert!(result.error.is_none(), "{}: {:?}", result.sql, result.error);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+use super::traits::{DbError, DbResult, MessageSeverity, QueryMessage, QueryResult};
+use std::cell::RefCell;
+use std::future::Future;
+use std::sync::LazyLock;
+use tracing::field::{Field, Visit};
+use tracing::instrument::WithSubscriber;
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::{Dispatch, Event, Level, Metadata, Subscriber};
+
+/// sqlx reports PostgreSQL notices only as tracing events on this target.
+const NOTICE_TARGET: &str = "sqlx::postgres::notice";
+
+tokio::task_local! {
+    static COLLECTED: RefCell<Vec<QueryMessage>>;
+}
+
+/// Created once, since every new dispatcher makes tracing rebuild the
+/// interest of all callsites.
+static NOTICES: LazyLock<Dispatch> = LazyLock::new(|| Dispatch::new(NoticeSubscriber));
+
+/// Runs a statement and attaches the notices the server sent while it ran.
+/// The events are raised while the connection is polled, so they arrive on
+/// the task that awaits the statement; the subscriber only stands in for the
+/// default one while the statement is polled. Notices sent before a failure
+/// are appended to the error.
+pub async fn with_notices<F>(statement: F) -> DbResult<QueryResult>
+where
+    F: Future<Output = DbResult<QueryResult>>,
+{
+    COLLECTED
+        .scope(RefCell::new(Vec::new()), async move {
+            let outcome = statement.with_subscriber(NOTICES.clone()).await;
+            let messages = COLLECTED.with(|collected| collected.take());
+            match outcome {
+                Ok(mut result) => {
+                    result.messages = messages;
+                    Ok(result)
+                }
+                Err(DbError::Query(error)) if !messages.is_empty() => {
+                    Err(DbError::Query(append_messages(error, &messages)))
+                }
+                Err(e) => Err(e),
+            }
+        })
+        .await
+}
+
+/// Appends server messages to an error, one per line after a blank one.
+pub fn append_messages(mut error: String, messages: &[QueryMessage]) -> String {
+    error.push('\n');
+    for message in messages {
+        let label = match message.severity {
+            MessageSeverity::Error => "ERROR",
+            MessageSeverity::Warning => "WARNING",
+            MessageSeverity::Notice => "NOTICE",
+            MessageSeverity::Info => "INFO",
+            MessageSeverity::Debug => "DEBUG",
+        };
+        error.push_str(&format!("\n{}: {}", label, message.text));
+    }
+    error
+}
+
+struct NoticeSubscriber;
+
+impl Subscriber for NoticeSubscriber {
+    fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+        if self.enabled(metadata) {
+            Interest::always()
+        } else {
+            Interest::never()
+        }
+    }
+
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.target() == NOTICE_TARGET
+    }
+
+    fn new_span(&self, _span: &Attributes<'_>) -> Id {
+        Id::from_u64(1)
+    }
+
+    fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+    fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+    fn event(&self, event: &Event<'_>) {
+        let mut text = MessageText::default();
+        event.record(&mut text);
+        // sqlx folds the server severity into a log level.
+        let severity = match *event.metadata().level() {
+            Level::ERROR => MessageSeverity::Error,
+            Level::WARN => MessageSeverity::Warning,
+            Level::INFO => MessageSeverity::Notice,
+            Level::DEBUG => MessageSeverity::Debug,
+            _ => MessageSeverity::Info,
+        };
+        let _ = COLLECTED.try_with(|collected| {
+            collected.borrow_mut().push(QueryMessage {
+                severity,
+                // sqlx passes on only the notice text, not its SQLSTATE.
+                code: None,
+                text: text.0,
+            })
+        });
+    }
+
+    fn enter(&self, _span: &Id) {}
+
+    fn exit(&self, _span: &Id) {}
+}
+
+#[derive(Default)]
+struct MessageText(String);
+
+impl Visit for MessageText {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0 = value.to_string();
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+        if field.name() == "message" {
+            self.0 = format!("{:?}", value);
+        }
+    }
+}
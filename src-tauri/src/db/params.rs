@@ -0,0 +1,197 @@
+use super::script::Scanner;
+use super::traits::{DatabaseType, DbError, DbResult, ParamValue, QueryParams};
+use std::collections::HashMap;
+
+/// Resolves the parameters passed with an ad-hoc query into the positional
+/// list the driver binds. Named `:name` references are rewritten to the
+/// backend's placeholder syntax; positional ones are passed through as typed.
+pub fn resolve_params(
+    sql: &str,
+    db_type: DatabaseType,
+    params: Option<QueryParams>,
+) -> DbResult<(String, Vec<ParamValue>)> {
+    match params {
+        None => Ok((sql.to_string(), Vec::new())),
+        Some(QueryParams::Positional(values)) => Ok((sql.to_string(), values)),
+        Some(QueryParams::Named(values)) => rewrite_named(sql, db_type, &values),
+    }
+}
+
+fn rewrite_named(
+    sql: &str,
+    db_type: DatabaseType,
+    values: &HashMap<String, ParamValue>,
+) -> DbResult<(String, Vec<ParamValue>)> {
+    let scanner = Scanner::new(sql, db_type);
+    let bytes = sql.as_bytes();
+    let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+
+    let mut out = String::with_capacity(sql.len());
+    let mut bound: Vec<ParamValue> = Vec::new();
+    // PostgreSQL placeholders are numbered, so a name used twice maps to the
+    // same `$n`; `?` placeholders need one value per occurrence.
+    let mut numbered: Vec<&str> = Vec::new();
+    let mut copied = 0;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if let Some((end, _)) = scanner.skip_non_code(i) {
+            i = end;
+            continue;
+        }
+
+        let is_param = bytes[i] == b':'
+            && bytes
+                .get(i + 1)
+                .is_some_and(|&b| b.is_ascii_alphabetic() || b == b'_')
+            && (i == 0 || bytes[i - 1] != b':');
+        if !is_param {
+            i += 1;
+            continue;
+        }
+
+        let mut end = i + 1;
+        while end < bytes.len() && is_ident(bytes[end]) {
+            end += 1;
+        }
+        let name = &sql[i + 1..end];
+        let value = values
+            .get(name)
+            .ok_or_else(|| DbError::InvalidOperation(format!("Missing value for :{}", name)))?;
+
+        out.push_str(&sql[copied..i]);
+        if db_type == DatabaseType::PostgreSQL {
+            let index = match numbered.iter().position(|n| *n == name) {
+                Some(index) => index,
+                None => {
+                    numbered.push(name);
+                    bound.push(value.clone());
+                    numbered.len() - 1
+                }
+            };
+            out.push_str(&format!("${}", index + 1));
+        } else {
+            bound.push(value.clone());
+            out.push('?');
+        }
+        copied = end;
+        i = end;
+    }
+
+    out.push_str(&sql[copied..]);
+    Ok((out, bound))
+}
+
+/// The bind value for a cell value sent from the grid. Arrays and objects are
+/// passed as their JSON text.
+pub fn json_param(value: &serde_json::Value) -> ParamValue {
+    match value {
+        serde_json::Value::Null => ParamValue::Null,
+        serde_json::Value::Bool(v) => ParamValue::Bool(*v),
+        serde_json::Value::Number(n) => match n.as_i64() {
+            Some(v) => ParamValue::Int(v),
+            None => ParamValue::Float(n.as_f64().unwrap_or_default()),
+        },
+        serde_json::Value::String(v) => ParamValue::Text(v.clone()),
+        other => ParamValue::Text(other.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+    use DatabaseType::*;
+
+    fn int(value: i64) -> Value {
+        json!({ "type": "int", "value": value })
+    }
+
+    fn text(value: &str) -> Value {
+        json!({ "type": "text", "value": value })
+    }
+
+    #[test]
+    fn resolves_params() {
+        let named = json!({ "a": int(1), "b": text("x"), "_c": int(3) });
+        let cases = [
+            ("SELECT :a", PostgreSQL, Value::Null, "SELECT :a", json!([])),
+            (
+                "SELECT $1, $2",
+                PostgreSQL,
+                json!([int(1), text("x")]),
+                "SELECT $1, $2",
+                json!([int(1), text("x")]),
+            ),
+            // PostgreSQL reuses the number of a repeated name.
+            (
+                "SELECT * FROM t WHERE a = :a AND b = :b OR c = :a",
+                PostgreSQL,
+                named.clone(),
+                "SELECT * FROM t WHERE a = $1 AND b = $2 OR c = $1",
+                json!([int(1), text("x")]),
+            ),
+            (
+                "SELECT * FROM t WHERE a = :a AND b = :b OR c = :a",
+                MySQL,
+                named.clone(),
+                "SELECT * FROM t WHERE a = ? AND b = ? OR c = ?",
+                json!([int(1), text("x"), int(1)]),
+            ),
+            (
+                "SELECT x::text, :_c FROM t WHERE y = :a",
+                PostgreSQL,
+                named.clone(),
+                "SELECT x::text, $1 FROM t WHERE y = $2",
+                json!([int(3), int(1)]),
+            ),
+            // Names inside literals, quoted identifiers and comments are text.
+            (
+                "SELECT ':b', \"a:b\" -- :b\nFROM t WHERE v = :a /* :b */",
+                SQLite,
+                named.clone(),
+                "SELECT ':b', \"a:b\" -- :b\nFROM t WHERE v = ? /* :b */",
+                json!([int(1)]),
+            ),
+            (
+                "SELECT `:b` FROM t WHERE id = :a # :b",
+                MySQL,
+                named.clone(),
+                "SELECT `:b` FROM t WHERE id = ? # :b",
+                json!([int(1)]),
+            ),
+            (
+                "SELECT $$ :b $$, $tag$ :b $tag$, :a",
+                PostgreSQL,
+                named.clone(),
+                "SELECT $$ :b $$, $tag$ :b $tag$, $1",
+                json!([int(1)]),
+            ),
+            (
+                "SELECT '12:30', :1 FROM t",
+                SQLite,
+                named.clone(),
+                "SELECT '12:30', :1 FROM t",
+                json!([]),
+            ),
+        ];
+        for (sql, db_type, params, expected_sql, expected_values) in cases {
+            let params = serde_json::from_value(params).unwrap();
+            let (actual_sql, actual_values) = resolve_params(sql, db_type, params).unwrap();
+            assert_eq!(actual_sql, expected_sql, "{}", sql);
+            assert_eq!(
+                serde_json::to_value(&actual_values).unwrap(),
+                expected_values,
+                "{}",
+                sql
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_missing_names() {
+        let params = serde_json::from_value(json!({ "a": int(1) })).unwrap();
+        let err = resolve_params("SELECT :a, :missing", MySQL, params).unwrap_err();
+        assert!(err.to_string().contains(":missing"), "{}", err);
+    }
+}
@@ -1,105 +1,187 @@
+use super::cancel::RunningQueries;
+use super::classify::classify;
+use super::explain::{self, QueryPlan};
+use super::filter::compile_filters;
+use super::notices::with_notices;
+use super::params::json_param;
+use super::script::{ScriptStatement, StatementResult};
+use super::session::{Session, SessionPool};
 use super::traits::*;
 use async_trait::async_trait;
-use sqlx::postgres::{PgConnectOptions, PgPool, PgPoolOptions, PgRow};
-use sqlx::{Column, Row};
-use std::str::FromStr;
-use std::sync::atomic::{AtomicBool, Ordering};
-use std::time::{Duration, Instant};
+use futures::TryStreamExt;
+use sqlx::postgres::types::{Oid, PgInterval, PgMoney, PgRange, PgTimeTz};
+use sqlx::postgres::{PgConnectOptions, PgPoolOptions, PgRow, PgSslMode};
+use sqlx::types::ipnetwork::IpNetwork;
+use sqlx::types::mac_address::MacAddress;
+use sqlx::types::{BigDecimal, Uuid};
+use sqlx::{Column, Executor, Postgres, Row, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::path::Path;
+use std::time::Instant;
 
 pub struct PostgresConnection {
-    pool: PgPool,
-    in_transaction: AtomicBool,
+    running: RunningQueries<i32>,
+    session: SessionPool<Postgres>,
 }
 
 impl PostgresConnection {
-    pub async fn new(connection_string: &str) -> DbResult<Self> {
-        let options = PgConnectOptions::from_str(connection_string)
-            .map_err(|e| DbError::Connection(e.to_string()))?
-            .ssl_mode(sqlx::postgres::PgSslMode::Prefer)
+    pub async fn new(params: &ConnectionParams) -> DbResult<Self> {
+        let tls = &params.tls;
+        let mut options = PgConnectOptions::new()
+            .host(&params.host)
+            .port(params.port)
+            .database(&params.database)
+            .username(&params.username)
+            .password(&params.password)
+            .ssl_mode(match tls.ssl_mode {
+                SslMode::Disable => PgSslMode::Disable,
+                SslMode::Prefer => PgSslMode::Prefer,
+                SslMode::Require => PgSslMode::Require,
+                SslMode::VerifyCa => PgSslMode::VerifyCa,
+                SslMode::VerifyFull => PgSslMode::VerifyFull,
+            })
             .statement_cache_capacity(0);
+        // sqlx looks for `.s.PGSQL.<port>` in the socket's directory.
+        if let Some(dir) = params.socket.as_deref().and_then(Path::parent) {
+            options = options.socket(dir);
+        }
+        if let Some(path) = &tls.ca_cert_path {
+            options = options.ssl_root_cert(path);
+        }
+        if let Some(path) = &tls.client_cert_path {
+            options = options.ssl_client_cert(path);
+        }
+        if let Some(path) = &tls.client_key_path {
+            options = options.ssl_client_key(path);
+        }
+        for (key, value) in &params.parameters {
+            options = match key.as_str() {
+                "connect_timeout" => options,
+                "application_name" => options.application_name(value),
+                "default_transaction_read_only" | "transaction_read_only" if params.read_only => {
+                    return Err(DbError::Connection(format!(
+                        "{} cannot be set on a read-only connection",
+                        key
+                    )))
+                }
+                _ => options.options([(key, value)]),
+            };
+        }
+        // Added last, since the server keeps the last value of a repeated
+        // setting.
+        if params.read_only {
+            options = options.options([("default_transaction_read_only", "on")]);
+        }
 
         let pool = PgPoolOptions::new()
             .max_connections(10)
-            .acquire_timeout(Duration::from_secs(10))
+            .acquire_timeout(params.connect_timeout()?)
             .connect_with(options)
             .await
             .map_err(|e| DbError::Connection(e.to_string()))?;
 
         Ok(Self {
-            pool,
-            in_transaction: AtomicBool::new(false),
+            running: RunningQueries::default(),
+            session: SessionPool::new(pool, params.read_only),
         })
     }
 
-    fn build_where_clause(&self, filters: &[FilterCondition]) -> (String, Vec<String>) {
-        let mut conditions = Vec::new();
-        let mut values = Vec::new();
-        let mut param_index = 1;
-
-        for filter in filters.iter() {
-            let condition = match filter.operator {
-                FilterOperator::Equals => {
-                    let placeholder = format!("${}", param_index);
-                    param_index += 1;
-                    values.push(filter.value.clone());
-                    format!("\"{}\" = {}", filter.column, placeholder)
-                }
-                FilterOperator::NotEquals => {
-                    let placeholder = format!("${}", param_index);
-                    param_index += 1;
-                    values.push(filter.value.clone());
-                    format!("\"{}\" != {}", filter.column, placeholder)
-                }
-                FilterOperator::Contains => {
-                    let placeholder = format!("${}", param_index);
-                    param_index += 1;
-                    values.push(format!("%{}%", filter.value));
-                    format!("\"{}\" ILIKE {}", filter.column, placeholder)
-                }
-                FilterOperator::StartsWith => {
-                    let placeholder = format!("${}", param_index);
-                    param_index += 1;
-                    values.push(format!("{}%", filter.value));
-                    format!("\"{}\" ILIKE {}", filter.column, placeholder)
-                }
-                FilterOperator::EndsWith => {
-                    let placeholder = format!("${}", param_index);
-                    param_index += 1;
-                    values.push(format!("%{}", filter.value));
-                    format!("\"{}\" ILIKE {}", filter.column, placeholder)
-                }
-                FilterOperator::GreaterThan => {
-                    let placeholder = format!("${}", param_index);
-                    param_index += 1;
-                    values.push(filter.value.clone());
-                    format!("\"{}\" > {}", filter.column, placeholder)
-                }
-                FilterOperator::LessThan => {
-                    let placeholder = format!("${}", param_index);
-                    param_index += 1;
-                    values.push(filter.value.clone());
-                    format!("\"{}\" < {}", filter.column, placeholder)
-                }
-                FilterOperator::IsNull => {
-                    format!("\"{}\" IS NULL", filter.column)
-                }
-                FilterOperator::IsNotNull => {
-                    format!("\"{}\" IS NOT NULL", filter.column)
-                }
-                FilterOperator::Raw => {
-                    format!("\"{}\" {}", filter.column, filter.value)
-                }
-            };
-            conditions.push(condition);
+    /// Matches one row by every column of its primary key. Key values are
+    /// bound after any already in `values`.
+    fn build_key_clause(
+        key: &HashMap<String, serde_json::Value>,
+        types: &HashMap<String, String>,
+        values: &mut Vec<ParamValue>,
+    ) -> DbResult<String> {
+        if key.is_empty() {
+            return Err(DbError::InvalidOperation(
+                "Rows can only be changed in tables with a primary key".to_string(),
+            ));
         }
+        let mut columns: Vec<_> = key.iter().collect();
+        columns.sort_by(|a, b| a.0.cmp(b.0));
+        let conditions: Vec<String> = columns
+            .into_iter()
+            .map(|(col, val)| {
+                if val.is_null() {
+                    format!("{} IS NULL", Self::quote_column(col))
+                } else {
+                    values.push(json_param(val));
+                    format!(
+                        "{} = {}",
+                        Self::quote_column(col),
+                        Self::typed_placeholder(values.len(), types.get(col))
+                    )
+                }
+            })
+            .collect();
+        Ok(conditions.join(" AND "))
+    }
 
-        if conditions.is_empty() {
-            (String::new(), values)
-        } else {
-            (format!("WHERE {}", conditions.join(" AND ")), values)
+    fn quote_column(col: &str) -> String {
+        format!("\"{}\"", col.replace('"', "\"\""))
+    }
+
+    /// `$n` cast to the column's declared type, since grid values arrive as
+    /// JSON strings and numbers rather than in the column's own type.
+    fn typed_placeholder(index: usize, data_type: Option<&String>) -> String {
+        match data_type {
+            Some(data_type) => format!("CAST(${} AS {})", index, data_type),
+            None => format!("${}", index),
         }
     }
 
+    /// Column types as `format_type` spells them, which also names enums,
+    /// domains and arrays.
+    async fn column_types(
+        conn: &mut sqlx::PgConnection,
+        schema: &str,
+        table: &str,
+    ) -> DbResult<HashMap<String, String>> {
+        let rows: Vec<(String, String)> = sqlx::query_as(
+            r#"
+            SELECT a.attname::text, format_type(a.atttypid, a.atttypmod)
+            FROM pg_attribute a
+            WHERE a.attrelid = to_regclass(quote_ident($1) || '.' || quote_ident($2))
+                AND a.attnum > 0
+                AND NOT a.attisdropped
+            "#,
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_all(&mut *conn)
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(rows.into_iter().collect())
+    }
+
+    /// Filter values are cast to the column type as `format_type` spells it:
+    /// information_schema drops lengths, and `character` alone would mean
+    /// `character(1)`. Enums and arrays keep their category so that they are
+    /// still compared as text.
+    async fn build_where_clause(
+        conn: &mut sqlx::PgConnection,
+        params: &FetchDataParams,
+        columns: &[ColumnInfo],
+    ) -> DbResult<(String, Vec<ParamValue>)> {
+        let Some(filters) = &params.filters else {
+            return Ok((String::new(), Vec::new()));
+        };
+        let types = Self::column_types(conn, &params.schema, &params.table).await?;
+        let columns: Vec<ColumnInfo> = columns
+            .iter()
+            .map(|column| {
+                let mut column = column.clone();
+                let by_category = matches!(column.data_type.as_str(), "USER-DEFINED" | "ARRAY");
+                if let Some(data_type) = types.get(&column.name).filter(|_| !by_category) {
+                    column.data_type = data_type.clone();
+                }
+                column
+            })
+            .collect();
+        compile_filters(filters, &columns, DatabaseType::PostgreSQL)
+    }
+
     fn build_order_clause(&self, sort: &[SortColumn]) -> String {
         if sort.is_empty() {
             return String::new();
@@ -174,6 +256,494 @@ impl PostgresConnection {
                 .unwrap_or(serde_json::Value::Null),
         }
     }
+
+    fn row_to_json(row: &PgRow) -> Vec<serde_json::Value> {
+        (0..row.len())
+            .map(|index| Self::decode_value(row, index))
+            .collect()
+    }
+
+    /// Decodes a result cell according to its column's type rather than by
+    /// trial and error, so ad-hoc results keep numerics, temporal, network,
+    /// range and array values. Unknown types fall back to their text form.
+    fn decode_value(row: &PgRow, index: usize) -> serde_json::Value {
+        use serde_json::Value;
+        match row.try_get_raw(index) {
+            Ok(raw) if !raw.is_null() => {}
+            _ => return Value::Null,
+        }
+        let type_info = row.columns()[index].type_info();
+        // The internal single-byte "char" type, not char(n), which sqlx also
+        // names CHAR.
+        if type_info.oid() == Some(Oid(18)) {
+            return Self::decode::<i8>(row, index, Value::from);
+        }
+        let type_name = type_info.name();
+        if let Some(element) = type_name.strip_suffix("[]") {
+            return Self::decode_array(row, index, element);
+        }
+
+        match type_name {
+            "BOOL" => Self::decode::<bool>(row, index, Value::from),
+            "CHAR" | "BPCHAR" => Self::decode::<String>(row, index, Value::from),
+            "INT2" => Self::decode::<i16>(row, index, Value::from),
+            "INT4" => Self::decode::<i32>(row, index, Value::from),
+            "INT8" => Self::decode::<i64>(row, index, Value::from),
+            "OID" => Self::decode::<Oid>(row, index, |v| Value::from(v.0)),
+            "FLOAT4" => Self::decode::<f32>(row, index, |v| Value::from(v as f64)),
+            "FLOAT8" => Self::decode::<f64>(row, index, Value::from),
+            "NUMERIC" => Self::decode::<BigDecimal>(row, index, |v| Value::String(v.to_string())),
+            "MONEY" => Self::decode::<PgMoney>(row, index, |v| {
+                Value::String(v.to_bigdecimal(2).to_string())
+            }),
+            "UUID" => Self::decode::<Uuid>(row, index, |v| Value::String(v.to_string())),
+            "JSON" | "JSONB" => Self::decode::<Value>(row, index, |v| v),
+            "BYTEA" => {
+                Self::decode::<Vec<u8>>(row, index, |v| Value::String(Self::format_bytea(&v)))
+            }
+            "DATE" => {
+                Self::decode::<chrono::NaiveDate>(row, index, |v| Value::String(v.to_string()))
+            }
+            "TIME" => {
+                Self::decode::<chrono::NaiveTime>(row, index, |v| Value::String(v.to_string()))
+            }
+            "TIMETZ" => Self::decode::<PgTimeTz>(row, index, |v| {
+                Value::String(format!("{}{}", v.time, v.offset))
+            }),
+            "TIMESTAMP" => {
+                Self::decode::<chrono::NaiveDateTime>(row, index, |v| Value::String(v.to_string()))
+            }
+            "TIMESTAMPTZ" => Self::decode::<chrono::DateTime<chrono::Utc>>(row, index, |v| {
+                Value::String(v.to_rfc3339())
+            }),
+            "INTERVAL" => {
+                Self::decode::<PgInterval>(row, index, |v| Value::String(Self::format_interval(&v)))
+            }
+            "INET" => {
+                Self::decode::<IpNetwork>(row, index, |v| Value::String(Self::format_inet(&v)))
+            }
+            "CIDR" => Self::decode::<IpNetwork>(row, index, |v| Value::String(v.to_string())),
+            "MACADDR" => Self::decode::<MacAddress>(row, index, |v| Value::String(v.to_string())),
+            "INT4RANGE" => Self::decode::<PgRange<i32>>(row, index, Self::format_range),
+            "INT8RANGE" => Self::decode::<PgRange<i64>>(row, index, Self::format_range),
+            "NUMRANGE" => Self::decode::<PgRange<BigDecimal>>(row, index, Self::format_range),
+            "DATERANGE" => {
+                Self::decode::<PgRange<chrono::NaiveDate>>(row, index, Self::format_range)
+            }
+            "TSRANGE" => {
+                Self::decode::<PgRange<chrono::NaiveDateTime>>(row, index, Self::format_range)
+            }
+            "TSTZRANGE" => Self::decode::<PgRange<chrono::DateTime<chrono::Utc>>>(
+                row,
+                index,
+                Self::format_range,
+            ),
+            _ => row
+                .try_get_unchecked::<String, _>(index)
+                .map(Value::from)
+                .unwrap_or(Value::Null),
+        }
+    }
+
+    fn decode_array(row: &PgRow, index: usize, element: &str) -> serde_json::Value {
+        use serde_json::Value;
+        match element {
+            "BOOL" => Self::decode_elements::<bool>(row, index, Value::from),
+            "INT2" => Self::decode_elements::<i16>(row, index, Value::from),
+            "INT4" => Self::decode_elements::<i32>(row, index, Value::from),
+            "INT8" => Self::decode_elements::<i64>(row, index, Value::from),
+            "FLOAT4" => Self::decode_elements::<f32>(row, index, |v| Value::from(v as f64)),
+            "FLOAT8" => Self::decode_elements::<f64>(row, index, Value::from),
+            "NUMERIC" => {
+                Self::decode_elements::<BigDecimal>(row, index, |v| Value::String(v.to_string()))
+            }
+            "UUID" => Self::decode_elements::<Uuid>(row, index, |v| Value::String(v.to_string())),
+            "JSON" | "JSONB" => Self::decode_elements::<Value>(row, index, |v| v),
+            "BYTEA" => Self::decode_elements::<Vec<u8>>(row, index, |v| {
+                Value::String(Self::format_bytea(&v))
+            }),
+            "DATE" => Self::decode_elements::<chrono::NaiveDate>(row, index, |v| {
+                Value::String(v.to_string())
+            }),
+            "TIMESTAMP" => Self::decode_elements::<chrono::NaiveDateTime>(row, index, |v| {
+                Value::String(v.to_string())
+            }),
+            "TIMESTAMPTZ" => {
+                Self::decode_elements::<chrono::DateTime<chrono::Utc>>(row, index, |v| {
+                    Value::String(v.to_rfc3339())
+                })
+            }
+            "INTERVAL" => Self::decode_elements::<PgInterval>(row, index, |v| {
+                Value::String(Self::format_interval(&v))
+            }),
+            "INET" => Self::decode_elements::<IpNetwork>(row, index, |v| {
+                Value::String(Self::format_inet(&v))
+            }),
+            "CIDR" => {
+                Self::decode_elements::<IpNetwork>(row, index, |v| Value::String(v.to_string()))
+            }
+            _ => Self::decode_elements::<String>(row, index, Value::from),
+        }
+    }
+
+    fn decode<'r, T>(
+        row: &'r PgRow,
+        index: usize,
+        to_json: impl FnOnce(T) -> serde_json::Value,
+    ) -> serde_json::Value
+    where
+        T: sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres>,
+    {
+        row.try_get::<T, _>(index)
+            .map(to_json)
+            .unwrap_or(serde_json::Value::Null)
+    }
+
+    fn decode_elements<'r, T>(
+        row: &'r PgRow,
+        index: usize,
+        to_json: impl Fn(T) -> serde_json::Value,
+    ) -> serde_json::Value
+    where
+        Vec<Option<T>>: sqlx::Decode<'r, Postgres> + sqlx::Type<Postgres>,
+    {
+        row.try_get::<Vec<Option<T>>, _>(index)
+            .map(|items| {
+                items
+                    .into_iter()
+                    .map(|item| item.map_or(serde_json::Value::Null, &to_json))
+                    .collect()
+            })
+            .unwrap_or(serde_json::Value::Null)
+    }
+
+    /// Formats bytes the way psql does, as `\x` followed by hex digits.
+    fn format_bytea(bytes: &[u8]) -> String {
+        let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+        format!("\\x{}", hex)
+    }
+
+    /// Omits the prefix length for single-host `inet` values, as psql does.
+    fn format_inet(network: &IpNetwork) -> String {
+        let host_prefix = if network.is_ipv4() { 32 } else { 128 };
+        if network.prefix() == host_prefix {
+            network.ip().to_string()
+        } else {
+            network.to_string()
+        }
+    }
+
+    fn format_interval(interval: &PgInterval) -> String {
+        let plural = |n: i32| if n.abs() == 1 { "" } else { "s" };
+        let (years, months) = (interval.months / 12, interval.months % 12);
+        let mut parts = Vec::new();
+        if years != 0 {
+            parts.push(format!("{} year{}", years, plural(years)));
+        }
+        if months != 0 {
+            parts.push(format!("{} mon{}", months, plural(months)));
+        }
+        if interval.days != 0 {
+            parts.push(format!("{} day{}", interval.days, plural(interval.days)));
+        }
+        if interval.microseconds != 0 || parts.is_empty() {
+            let sign = if interval.microseconds < 0 { "-" } else { "" };
+            let micros = interval.microseconds.unsigned_abs();
+            let seconds = micros / 1_000_000;
+            let mut time = format!(
+                "{}{:02}:{:02}:{:02}",
+                sign,
+                seconds / 3600,
+                seconds / 60 % 60,
+                seconds % 60
+            );
+            if micros % 1_000_000 != 0 {
+                time.push_str(&format!(".{:06}", micros % 1_000_000));
+            }
+            parts.push(time);
+        }
+        parts.join(" ")
+    }
+
+    fn format_range<T: std::fmt::Display>(range: PgRange<T>) -> serde_json::Value {
+        use std::ops::Bound;
+        let (open, start) = match range.start {
+            Bound::Included(v) => ('[', v.to_string()),
+            Bound::Excluded(v) => ('(', v.to_string()),
+            Bound::Unbounded => ('(', String::new()),
+        };
+        let (close, end) = match range.end {
+            Bound::Included(v) => (']', v.to_string()),
+            Bound::Excluded(v) => (')', v.to_string()),
+            Bound::Unbounded => (')', String::new()),
+        };
+        serde_json::Value::String(format!("{}{},{}{}", open, start, end, close))
+    }
+
+    /// Column names, engine types and nullability as reported by the
+    /// prepared statement, so they are known even when no rows come back.
+    /// Columns read straight from a table also carry their source.
+    async fn describe_columns(
+        conn: &mut sqlx::PgConnection,
+        sql: &str,
+    ) -> Option<Vec<ResultColumn>> {
+        use sqlx::Executor;
+        let desc = (&mut *conn).describe(sql).await.ok()?;
+        let relations: Vec<Oid> = desc
+            .columns()
+            .iter()
+            .filter_map(|col| col.relation_id())
+            .collect();
+        let sources = Self::column_sources(conn, &relations).await;
+
+        let columns = desc
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, col)| {
+                let mut column =
+                    ResultColumn::new(col.name(), col.type_info().name(), desc.nullable(i));
+                let source = col
+                    .relation_id()
+                    .zip(col.relation_attribute_no())
+                    .and_then(|(relation, attnum)| sources.get(&(relation.0, attnum)));
+                if let Some((schema, table, name)) = source {
+                    column.source_schema = Some(schema.clone());
+                    column.source_table = Some(table.clone());
+                    column.source_column = Some(name.clone());
+                }
+                column
+            })
+            .collect();
+        Some(columns)
+    }
+
+    /// Resolves the relation OIDs and attribute numbers PostgreSQL reports
+    /// for result columns to schema, table and column names.
+    async fn column_sources(
+        conn: &mut sqlx::PgConnection,
+        relations: &[Oid],
+    ) -> HashMap<(u32, i16), (String, String, String)> {
+        if relations.is_empty() {
+            return HashMap::new();
+        }
+        sqlx::query_as::<_, (Oid, i16, String, String, String)>(
+            "SELECT a.attrelid, a.attnum, n.nspname, c.relname, a.attname
+             FROM pg_attribute a
+             JOIN pg_class c ON c.oid = a.attrelid
+             JOIN pg_namespace n ON n.oid = c.relnamespace
+             WHERE a.attrelid = ANY($1) AND a.attnum > 0",
+        )
+        .bind(relations)
+        .fetch_all(&mut *conn)
+        .await
+        .map(|rows| {
+            rows.into_iter()
+                .map(|(relation, attnum, schema, table, column)| {
+                    ((relation.0, attnum), (schema, table, column))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+    }
+
+    fn row_columns(row: &PgRow) -> Vec<ResultColumn> {
+        row.columns()
+            .iter()
+            .map(|col| ResultColumn::new(col.name(), col.type_info().name(), None))
+            .collect()
+    }
+
+    async fn track_query(&self, conn: &mut sqlx::PgConnection, query_id: &str) -> DbResult<()> {
+        let pid: i32 = sqlx::query_scalar("SELECT pg_backend_pid()")
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        self.running.register(query_id, pid);
+        Ok(())
+    }
+
+    async fn stream_rows(
+        &self,
+        conn: &mut sqlx::PgConnection,
+        sql: &str,
+        batch_size: usize,
+        on_event: &mut (dyn FnMut(QueryEvent) + Send),
+    ) -> DbResult<()> {
+        let start = Instant::now();
+        let columns = Self::describe_columns(conn, sql).await.unwrap_or_default();
+        on_event(QueryEvent::Columns { columns });
+
+        let mut rows = sqlx::query(sql).fetch(&mut *conn);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut row_count = 0u64;
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?
+        {
+            batch.push(Self::row_to_json(&row));
+            row_count += 1;
+            if batch.len() >= batch_size {
+                on_event(QueryEvent::Rows {
+                    rows: std::mem::replace(&mut batch, Vec::with_capacity(batch_size)),
+                });
+            }
+        }
+        if !batch.is_empty() {
+            on_event(QueryEvent::Rows { rows: batch });
+        }
+
+        on_event(QueryEvent::Done {
+            row_count,
+            execution_time_ms: start.elapsed().as_millis(),
+        });
+        Ok(())
+    }
+
+    fn bind_params<'q>(
+        mut query: sqlx::query::Query<'q, Postgres, sqlx::postgres::PgArguments>,
+        params: &'q [ParamValue],
+    ) -> sqlx::query::Query<'q, Postgres, sqlx::postgres::PgArguments> {
+        for param in params {
+            query = match param {
+                ParamValue::Null => query.bind(None::<String>),
+                ParamValue::Bool(v) => query.bind(*v),
+                ParamValue::Int(v) => query.bind(*v),
+                ParamValue::Float(v) => query.bind(*v),
+                ParamValue::Text(v) => query.bind(v.as_str()),
+                ParamValue::Json(v) => query.bind(sqlx::types::Json(v)),
+            };
+        }
+        query
+    }
+
+    async fn run_query(
+        &self,
+        conn: &mut sqlx::PgConnection,
+        sql: &str,
+        params: &[ParamValue],
+        max_rows: Option<u64>,
+    ) -> DbResult<QueryResult> {
+        let start = Instant::now();
+
+        let info = classify(sql, DatabaseType::PostgreSQL);
+        self.session.ensure_allowed(sql, DatabaseType::PostgreSQL)?;
+
+        if info.returns_rows {
+            let described = Self::describe_columns(conn, sql).await;
+
+            // A read stops one row past the cap and drops the rest unread. A
+            // write runs to completion anyway, so its rows are still counted.
+            let count_all = info.kind.modifies_data();
+            let mut rows = Vec::new();
+            let mut row_count = 0u64;
+            let mut truncated = false;
+            let mut stream = Self::bind_params(sqlx::query(sql), params).fetch(&mut *conn);
+            while let Some(row) = stream
+                .try_next()
+                .await
+                .map_err(|e| DbError::Query(e.to_string()))?
+            {
+                if max_rows.is_some_and(|max| rows.len() as u64 >= max) {
+                    truncated = true;
+                    if !count_all {
+                        break;
+                    }
+                } else {
+                    rows.push(row);
+                }
+                row_count += 1;
+            }
+            drop(stream);
+
+            let execution_time_ms = start.elapsed().as_millis();
+
+            let columns = described
+                .or_else(|| rows.first().map(Self::row_columns))
+                .unwrap_or_default();
+
+            let result_rows: Vec<Vec<serde_json::Value>> =
+                rows.iter().map(Self::row_to_json).collect();
+
+            Ok(QueryResult {
+                columns,
+                rows: result_rows,
+                rows_affected: row_count,
+                execution_time_ms,
+                truncated,
+                messages: Vec::new(),
+            })
+        } else {
+            let result = Self::bind_params(sqlx::query(sql), params)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| DbError::Query(e.to_string()))?;
+
+            let execution_time_ms = start.elapsed().as_millis();
+
+            Ok(QueryResult {
+                columns: vec![],
+                rows: vec![],
+                rows_affected: result.rows_affected(),
+                execution_time_ms,
+                truncated: false,
+                messages: Vec::new(),
+            })
+        }
+    }
+
+    /// Runs an ad-hoc statement under `limits`. Inside the user's transaction
+    /// the timeout is set with `SET LOCAL` and the previous value is put back
+    /// afterwards; a pooled session sets it for the session and resets it
+    /// before the connection goes back to the pool.
+    async fn run_limited(
+        &self,
+        conn: &mut Session<'_, Postgres>,
+        sql: &str,
+        params: &[ParamValue],
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let Some(timeout) = limits.timeout_ms else {
+            return self.run_query(conn, sql, params, limits.max_rows).await;
+        };
+
+        if let Session::Pinned(_) = conn {
+            let previous: String =
+                sqlx::query_scalar("SELECT current_setting('statement_timeout')")
+                    .fetch_one(&mut **conn)
+                    .await
+                    .map_err(|e| DbError::Query(e.to_string()))?;
+            (&mut **conn)
+                .execute(format!("SET LOCAL statement_timeout = {}", timeout).as_str())
+                .await
+                .map_err(|e| DbError::Query(e.to_string()))?;
+            let result = self.run_query(conn, sql, params, limits.max_rows).await;
+            // A failed statement aborts the transaction, and rolling it back
+            // discards the local setting anyway.
+            if result.is_ok() {
+                sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+                    .bind(previous)
+                    .execute(&mut **conn)
+                    .await
+                    .map_err(|e| DbError::Query(e.to_string()))?;
+            }
+            return result;
+        }
+
+        (&mut **conn)
+            .execute(format!("SET statement_timeout = {}", timeout).as_str())
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        let result = self.run_query(conn, sql, params, limits.max_rows).await;
+        let reset = (&mut **conn).execute("RESET statement_timeout").await;
+        // Inside a dry run's transaction a failed statement makes the RESET
+        // fail as well. The rollback undoes the SET anyway, so the statement's
+        // own error is the one reported.
+        let result = result?;
+        reset.map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(result)
+    }
 }
 
 #[async_trait]
@@ -183,14 +753,16 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn test_connection(&self) -> DbResult<()> {
+        let mut conn = self.session.acquire().await?;
         sqlx::query("SELECT 1")
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| DbError::Connection(e.to_string()))?;
         Ok(())
     }
 
     async fn get_schemas(&self) -> DbResult<Vec<SchemaInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT schema_name 
@@ -199,7 +771,7 @@ impl DbConnection for PostgresConnection {
             ORDER BY schema_name
             "#,
         )
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -212,6 +784,7 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn get_tables(&self, schema: &str) -> DbResult<Vec<TableInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT table_schema, table_name, table_type
@@ -221,7 +794,7 @@ impl DbConnection for PostgresConnection {
             "#,
         )
         .bind(schema)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -236,6 +809,7 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn get_columns(&self, schema: &str, table: &str) -> DbResult<Vec<ColumnInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT 
@@ -243,10 +817,11 @@ impl DbConnection for PostgresConnection {
                 c.data_type,
                 c.is_nullable,
                 c.column_default,
-                CASE WHEN pk.column_name IS NOT NULL THEN true ELSE false END as is_primary_key
+                CASE WHEN pk.column_name IS NOT NULL THEN true ELSE false END as is_primary_key,
+                pk.ordinal_position::int4 as key_ordinal
             FROM information_schema.columns c
             LEFT JOIN (
-                SELECT ku.column_name
+                SELECT ku.column_name, ku.ordinal_position
                 FROM information_schema.table_constraints tc
                 JOIN information_schema.key_column_usage ku
                     ON tc.constraint_name = ku.constraint_name
@@ -261,7 +836,7 @@ impl DbConnection for PostgresConnection {
         )
         .bind(schema)
         .bind(table)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -274,6 +849,7 @@ impl DbConnection for PostgresConnection {
                     data_type: row.get("data_type"),
                     is_nullable: nullable == "YES",
                     is_primary_key: row.get("is_primary_key"),
+                    primary_key_ordinal: row.get::<Option<i32>, _>("key_ordinal").map(|n| n as u32),
                     default_value: row.get("column_default"),
                 }
             })
@@ -281,6 +857,7 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn get_indexes(&self, schema: &str, table: &str) -> DbResult<Vec<IndexInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT 
@@ -300,7 +877,7 @@ impl DbConnection for PostgresConnection {
         )
         .bind(schema)
         .bind(table)
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -320,12 +897,10 @@ impl DbConnection for PostgresConnection {
 
     async fn get_table_data(&self, params: FetchDataParams) -> DbResult<TableData> {
         let columns = self.get_columns(&params.schema, &params.table).await?;
+        let mut conn = self.session.acquire().await?;
 
-        let (where_clause, _filter_values) = params
-            .filters
-            .as_ref()
-            .map(|f| self.build_where_clause(f))
-            .unwrap_or_default();
+        let (where_clause, filter_values) =
+            Self::build_where_clause(&mut conn, &params, &columns).await?;
 
         let order_clause = params
             .sort
@@ -337,8 +912,8 @@ impl DbConnection for PostgresConnection {
             "SELECT COUNT(*) as count FROM \"{}\".\"{}\" {}",
             params.schema, params.table, where_clause
         );
-        let count_row = sqlx::query(&count_query)
-            .fetch_one(&self.pool)
+        let count_row = Self::bind_params(sqlx::query(&count_query), &filter_values)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         let total_count: i64 = count_row.get("count");
@@ -347,8 +922,8 @@ impl DbConnection for PostgresConnection {
             "SELECT * FROM \"{}\".\"{}\" {} {} LIMIT {} OFFSET {}",
             params.schema, params.table, where_clause, order_clause, params.limit, params.offset
         );
-        let rows = sqlx::query(&data_query)
-            .fetch_all(&self.pool)
+        let rows = Self::bind_params(sqlx::query(&data_query), &filter_values)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -369,85 +944,108 @@ impl DbConnection for PostgresConnection {
         })
     }
 
-    async fn execute_query(&self, sql: &str) -> DbResult<QueryResult> {
-        let start = Instant::now();
-
-        let sql_lower = sql.trim().to_lowercase();
-        let is_select = sql_lower.starts_with("select") || sql_lower.starts_with("with");
-
-        if is_select {
-            use sqlx::Executor;
-            let describe = self.pool.describe(sql).await.ok();
-
-            let rows = sqlx::query(sql)
-                .fetch_all(&self.pool)
-                .await
-                .map_err(|e| DbError::Query(e.to_string()))?;
-
-            let execution_time_ms = start.elapsed().as_millis();
+    async fn execute_query(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        query_id: Option<&str>,
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let mut conn = self.session.acquire().await?;
+        let Some(query_id) = query_id else {
+            return with_notices(self.run_limited(&mut conn, sql, params, limits)).await;
+        };
 
-            let columns: Vec<String> = if !rows.is_empty() {
-                rows[0]
-                    .columns()
-                    .iter()
-                    .map(|c| c.name().to_string())
-                    .collect()
-            } else if let Some(desc) = describe {
-                desc.columns()
-                    .iter()
-                    .map(|c| c.name().to_string())
-                    .collect()
-            } else {
-                vec![]
-            };
+        self.track_query(&mut conn, query_id).await?;
+        let result = with_notices(self.run_limited(&mut conn, sql, params, limits)).await;
+        self.running.finish(query_id, result).await
+    }
 
-            let mut result_rows: Vec<Vec<serde_json::Value>> = Vec::new();
-            for row in &rows {
-                let mut row_data: Vec<serde_json::Value> = Vec::new();
-                for col in row.columns() {
-                    let value: serde_json::Value = row
-                        .try_get::<String, _>(col.name())
-                        .map(serde_json::Value::from)
-                        .or_else(|_| {
-                            row.try_get::<i64, _>(col.name())
-                                .map(serde_json::Value::from)
-                        })
-                        .or_else(|_| {
-                            row.try_get::<f64, _>(col.name())
-                                .map(serde_json::Value::from)
-                        })
-                        .or_else(|_| {
-                            row.try_get::<bool, _>(col.name())
-                                .map(serde_json::Value::from)
-                        })
-                        .unwrap_or(serde_json::Value::Null);
-                    row_data.push(value);
-                }
-                result_rows.push(row_data);
+    async fn execute_script(
+        &self,
+        statements: &[ScriptStatement],
+        stop_on_error: bool,
+    ) -> DbResult<Vec<StatementResult>> {
+        // Every statement runs on the same session so that SET, temporary
+        // tables and explicit BEGIN/COMMIT carry over between them.
+        let mut conn = self.session.acquire().await?;
+        let mut results = Vec::with_capacity(statements.len());
+        for statement in statements {
+            let outcome = with_notices(self.run_query(&mut conn, &statement.sql, &[], None)).await;
+            let failed = outcome.is_err();
+            results.push(StatementResult::new(statement, outcome));
+            if failed && stop_on_error {
+                break;
             }
+        }
+        conn.rollback_unpinned().await;
+        Ok(results)
+    }
 
-            let rows_affected = result_rows.len() as u64;
-            Ok(QueryResult {
-                columns,
-                rows: result_rows,
-                rows_affected,
-                execution_time_ms,
-            })
+    async fn explain_query(&self, sql: &str, analyze: bool) -> DbResult<QueryPlan> {
+        let sql = sql.trim().trim_end_matches(';');
+        // ANALYZE really runs the statement, so writes are rolled back.
+        let discard = analyze && classify(sql, DatabaseType::PostgreSQL).kind.modifies_data();
+        if discard {
+            self.session.ensure_writable()?;
+        }
+        let options = if analyze {
+            "FORMAT JSON, ANALYZE, BUFFERS"
         } else {
-            let result = sqlx::query(sql)
-                .execute(&self.pool)
-                .await
-                .map_err(|e| DbError::Query(e.to_string()))?;
-
-            let execution_time_ms = start.elapsed().as_millis();
+            "FORMAT JSON"
+        };
+        let explain = format!("EXPLAIN ({}) {}", options, sql);
 
-            Ok(QueryResult {
-                columns: vec![],
-                rows: vec![],
-                rows_affected: result.rows_affected(),
-                execution_time_ms,
-            })
+        let mut conn = self.session.acquire().await?;
+        if discard {
+            conn.begin_scratch().await?;
+        }
+        let output = sqlx::query_scalar::<_, serde_json::Value>(&explain)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()));
+        if discard {
+            conn.discard_scratch().await?;
         }
+        explain::parse_postgres(output?, analyze)
+    }
+
+    async fn dry_run(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let mut conn = self.session.acquire_writable().await?;
+        conn.begin_scratch().await?;
+        let outcome = with_notices(self.run_limited(&mut conn, sql, params, limits)).await;
+        conn.discard_scratch().await?;
+        outcome
+    }
+
+    async fn stream_query(
+        &self,
+        sql: &str,
+        query_id: &str,
+        batch_size: usize,
+        on_event: &mut (dyn FnMut(QueryEvent) + Send),
+    ) -> DbResult<()> {
+        self.session.ensure_allowed(sql, DatabaseType::PostgreSQL)?;
+        let mut conn = self.session.acquire().await?;
+        self.track_query(&mut conn, query_id).await?;
+        let result = self.stream_rows(&mut conn, sql, batch_size, on_event).await;
+        self.running.finish(query_id, result).await
+    }
+
+    async fn cancel_query(&self, query_id: &str) -> DbResult<()> {
+        let mut conn = self.session.acquire_unpinned().await?;
+        let (pid, _pending) = self.running.cancel(query_id, |pid| *pid)?;
+        sqlx::query("SELECT pg_cancel_backend($1)")
+            .bind(pid)
+            .execute(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(())
     }
 
     async fn get_distinct_values(
@@ -457,6 +1055,7 @@ impl DbConnection for PostgresConnection {
         column: &str,
         limit: Option<u32>,
     ) -> DbResult<Vec<serde_json::Value>> {
+        let mut conn = self.session.acquire().await?;
         let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
         let sql = format!(
             "SELECT DISTINCT \"{}\" FROM \"{}\".\"{}\" WHERE \"{}\" IS NOT NULL ORDER BY \"{}\"{}",
@@ -464,7 +1063,7 @@ impl DbConnection for PostgresConnection {
         );
 
         let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -479,42 +1078,61 @@ impl DbConnection for PostgresConnection {
         Ok(values)
     }
 
+    async fn estimate_row_count(&self, schema: Option<&str>, table: &str) -> DbResult<Option<u64>> {
+        let mut conn = self.session.acquire().await?;
+        // Unqualified names resolve through the search path, as they would
+        // in the statement itself.
+        let estimate: Option<f32> = sqlx::query_scalar(
+            r#"
+            SELECT c.reltuples
+            FROM pg_class c
+            WHERE c.oid = to_regclass(
+                CASE WHEN $1::text IS NULL THEN quote_ident($2)
+                     ELSE quote_ident($1) || '.' || quote_ident($2) END
+            )
+            "#,
+        )
+        .bind(schema)
+        .bind(table)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(|e| DbError::Query(e.to_string()))?;
+
+        // reltuples is -1 until the table has been vacuumed or analyzed.
+        Ok(estimate.filter(|rows| *rows >= 0.0).map(|rows| rows as u64))
+    }
+
     async fn update_row(&self, update: RowUpdate) -> DbResult<u64> {
-        let format_value = |v: &serde_json::Value| -> String {
-            if v.is_null() {
-                "NULL".to_string()
-            } else if v.is_number() {
-                v.to_string()
-            } else if v.is_boolean() {
-                v.to_string()
-            } else if v.is_string() {
-                let s = v.as_str().unwrap();
-                format!("'{}'", s.replace('\'', "''"))
-            } else {
-                let s = v.to_string();
-                format!("'{}'", s.replace('\'', "''"))
-            }
-        };
+        let mut conn = self.session.acquire_writable().await?;
 
+        let types = Self::column_types(&mut conn, &update.schema, &update.table).await?;
+
+        let mut values = Vec::new();
         let set_clauses: Vec<String> = update
             .updates
             .iter()
-            .map(|(col, val)| format!("\"{}\" = {}", col, format_value(val)))
+            .map(|(col, val)| {
+                values.push(json_param(val));
+                format!(
+                    "{} = {}",
+                    Self::quote_column(col),
+                    Self::typed_placeholder(values.len(), types.get(col))
+                )
+            })
             .collect();
 
-        let pk_formatted = format_value(&update.primary_key_value);
+        let key_clause = Self::build_key_clause(&update.primary_key, &types, &mut values)?;
 
         let sql = format!(
-            "UPDATE \"{}\".\"{}\" SET {} WHERE \"{}\" = {}",
+            "UPDATE \"{}\".\"{}\" SET {} WHERE {}",
             update.schema,
             update.table,
             set_clauses.join(", "),
-            update.primary_key_column,
-            pk_formatted
+            key_clause
         );
 
-        let result = sqlx::query(&sql)
-            .execute(&self.pool)
+        let result = Self::bind_params(sqlx::query(&sql), &values)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -522,6 +1140,7 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn insert_row(&self, insert: RowInsert) -> DbResult<serde_json::Value> {
+        let mut conn = self.session.acquire_writable().await?;
         let columns: Vec<String> = insert.values.keys().map(|k| format!("\"{}\"", k)).collect();
 
         let values: Vec<String> = insert
@@ -555,7 +1174,7 @@ impl DbConnection for PostgresConnection {
         let query = sqlx::query(&sql);
 
         let row = query
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -573,25 +1192,18 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn delete_row(&self, delete: RowDelete) -> DbResult<u64> {
-        let pk_formatted = if delete.primary_key_value.is_null() {
-            "NULL".to_string()
-        } else if delete.primary_key_value.is_number() {
-            delete.primary_key_value.to_string()
-        } else if delete.primary_key_value.is_string() {
-            let s = delete.primary_key_value.as_str().unwrap();
-            format!("'{}'", s.replace('\'', "''"))
-        } else {
-            let s = delete.primary_key_value.to_string();
-            format!("'{}'", s.replace('\'', "''"))
-        };
+        let mut conn = self.session.acquire_writable().await?;
+        let types = Self::column_types(&mut conn, &delete.schema, &delete.table).await?;
+        let mut values = Vec::new();
+        let key_clause = Self::build_key_clause(&delete.primary_key, &types, &mut values)?;
 
         let sql = format!(
-            "DELETE FROM \"{}\".\"{}\" WHERE \"{}\" = {}",
-            delete.schema, delete.table, delete.primary_key_column, pk_formatted
+            "DELETE FROM \"{}\".\"{}\" WHERE {}",
+            delete.schema, delete.table, key_clause
         );
 
-        let result = sqlx::query(&sql)
-            .execute(&self.pool)
+        let result = Self::bind_params(sqlx::query(&sql), &values)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -599,41 +1211,45 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn create_schema(&self, name: &str) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let sql = format!("CREATE SCHEMA \"{}\"", name);
         sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         Ok(())
     }
 
     async fn drop_schema(&self, name: &str, cascade: bool) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let sql = if cascade {
             format!("DROP SCHEMA \"{}\" CASCADE", name)
         } else {
             format!("DROP SCHEMA \"{}\"", name)
         };
         sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         Ok(())
     }
 
     async fn drop_table(&self, schema: &str, table: &str, cascade: bool) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let sql = if cascade {
             format!("DROP TABLE \"{}\".\"{}\" CASCADE", schema, table)
         } else {
             format!("DROP TABLE \"{}\".\"{}\"", schema, table)
         };
         sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         Ok(())
     }
 
     async fn alter_table(&self, params: AlterTableParams) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let table_name = format!("\"{}\".\"{}\"", params.schema, params.table);
 
         for change in params.changes {
@@ -677,7 +1293,7 @@ impl DbConnection for PostgresConnection {
             };
 
             sqlx::query(&sql)
-                .execute(&self.pool)
+                .execute(&mut *conn)
                 .await
                 .map_err(|e| DbError::Query(e.to_string()))?;
         }
@@ -686,38 +1302,23 @@ impl DbConnection for PostgresConnection {
     }
 
     async fn begin_transaction(&self) -> DbResult<()> {
-        sqlx::query("BEGIN")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(true, Ordering::SeqCst);
-        Ok(())
+        self.session.begin("BEGIN").await
     }
 
     async fn commit(&self) -> DbResult<()> {
-        sqlx::query("COMMIT")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(false, Ordering::SeqCst);
-        Ok(())
+        self.session.end("COMMIT").await
     }
 
     async fn rollback(&self) -> DbResult<()> {
-        sqlx::query("ROLLBACK")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(false, Ordering::SeqCst);
-        Ok(())
+        self.session.end("ROLLBACK").await
     }
 
     async fn in_transaction(&self) -> bool {
-        self.in_transaction.load(Ordering::SeqCst)
+        self.session.in_transaction().await
     }
 
     async fn close(&self) -> DbResult<()> {
-        self.pool.close().await;
+        self.session.close().await;
         Ok(())
     }
 }
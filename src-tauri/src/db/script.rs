@@ -0,0 +1,372 @@
+use super::traits::{DatabaseType, DbResult, QueryResult};
+use serde::Serialize;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ScriptStatement {
+    pub sql: String,
+    pub start_line: usize,
+    pub end_line: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct StatementResult {
+    pub sql: String,
+    pub start_line: usize,
+    pub end_line: usize,
+    pub result: Option<QueryResult>,
+    pub error: Option<String>,
+}
+
+impl StatementResult {
+    pub fn new(statement: &ScriptStatement, outcome: DbResult<QueryResult>) -> Self {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(e) => (None, Some(e.to_string())),
+        };
+        Self {
+            sql: statement.sql.clone(),
+            start_line: statement.start_line,
+            end_line: statement.end_line,
+            result,
+            error,
+        }
+    }
+}
+
+/// Byte-level helpers shared by the statement splitter and the named
+/// parameter rewriter for stepping over text that is not SQL code.
+pub(super) struct Scanner<'a> {
+    bytes: &'a [u8],
+    db_type: DatabaseType,
+}
+
+struct Splitter<'a> {
+    sql: &'a str,
+    scanner: Scanner<'a>,
+    line_starts: Vec<usize>,
+    statements: Vec<ScriptStatement>,
+}
+
+/// Splits a script into statements on the active delimiter, skipping over
+/// string literals, quoted identifiers, comments and PostgreSQL dollar-quoted
+/// bodies. MySQL `DELIMITER` directives are honoured and not returned.
+pub fn split_statements(sql: &str, db_type: DatabaseType) -> Vec<ScriptStatement> {
+    let line_starts = std::iter::once(0)
+        .chain(sql.match_indices('\n').map(|(i, _)| i + 1))
+        .collect();
+    let mut splitter = Splitter {
+        sql,
+        scanner: Scanner::new(sql, db_type),
+        line_starts,
+        statements: Vec::new(),
+    };
+    splitter.run();
+    splitter.statements
+}
+
+impl Splitter<'_> {
+    fn run(&mut self) {
+        let bytes = self.scanner.bytes;
+        let len = bytes.len();
+        let mut delimiter = ";".to_string();
+        let mut stmt_start = 0;
+        let mut has_code = false;
+        let mut at_line_start = true;
+        let mut i = 0;
+
+        while i < len {
+            if at_line_start && self.scanner.db_type == DatabaseType::MySQL {
+                let line_end = self.scanner.find_byte(b'\n', i);
+                if let Some(new_delimiter) = Self::delimiter_directive(&self.sql[i..line_end]) {
+                    if has_code {
+                        self.push(stmt_start, i);
+                    }
+                    delimiter = new_delimiter.to_string();
+                    has_code = false;
+                    stmt_start = line_end;
+                    i = line_end;
+                    continue;
+                }
+            }
+            at_line_start = bytes[i] == b'\n';
+
+            if let Some((end, is_code)) = self.scanner.skip_non_code(i) {
+                has_code |= is_code;
+                i = end;
+            } else if bytes[i..].starts_with(delimiter.as_bytes()) {
+                if has_code {
+                    self.push(stmt_start, i);
+                }
+                has_code = false;
+                i += delimiter.len();
+                stmt_start = i;
+            } else {
+                has_code |= !bytes[i].is_ascii_whitespace();
+                i += 1;
+            }
+        }
+
+        if has_code {
+            self.push(stmt_start, len);
+        }
+    }
+
+    fn push(&mut self, start: usize, end: usize) {
+        let raw = &self.sql[start..end];
+        let sql = raw.trim();
+        let start = start + (raw.len() - raw.trim_start().len());
+        let end = start + sql.len();
+        self.statements.push(ScriptStatement {
+            sql: sql.to_string(),
+            start_line: self.line_of(start),
+            end_line: self.line_of(end.saturating_sub(1).max(start)),
+        });
+    }
+
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+
+    fn delimiter_directive(line: &str) -> Option<&str> {
+        let line = line.trim();
+        let keyword = line.get(..9)?;
+        if !keyword.eq_ignore_ascii_case("delimiter") {
+            return None;
+        }
+        let rest = &line[9..];
+        if !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        Some(rest.trim()).filter(|d| !d.is_empty())
+    }
+}
+
+impl<'a> Scanner<'a> {
+    pub(super) fn new(sql: &'a str, db_type: DatabaseType) -> Self {
+        Self {
+            bytes: sql.as_bytes(),
+            db_type,
+        }
+    }
+
+    /// If a string literal, quoted identifier, comment or dollar-quoted body
+    /// starts at `i`, returns the index just past it and whether it counts as
+    /// code (literals do, comments do not).
+    pub(super) fn skip_non_code(&self, i: usize) -> Option<(usize, bool)> {
+        let c = self.bytes[i];
+        let next = self.bytes.get(i + 1).copied();
+        match c {
+            b'\'' | b'"' | b'`' => Some((self.skip_quoted(i, c), true)),
+            b'-' if next == Some(b'-') && self.is_dash_comment(i) => {
+                Some((self.find_byte(b'\n', i), false))
+            }
+            b'#' if self.db_type == DatabaseType::MySQL => Some((self.find_byte(b'\n', i), false)),
+            b'/' if next == Some(b'*') => Some((self.skip_block_comment(i), false)),
+            b'$' if self.db_type == DatabaseType::PostgreSQL => {
+                Some((self.skip_dollar_quoted(i), true))
+            }
+            _ => None,
+        }
+    }
+
+    fn find_byte(&self, byte: u8, from: usize) -> usize {
+        self.bytes[from..]
+            .iter()
+            .position(|&b| b == byte)
+            .map_or(self.bytes.len(), |p| from + p)
+    }
+
+    /// MySQL only treats `--` as a comment when followed by whitespace.
+    fn is_dash_comment(&self, i: usize) -> bool {
+        self.db_type != DatabaseType::MySQL
+            || self.bytes.get(i + 2).is_none_or(u8::is_ascii_whitespace)
+    }
+
+    fn skip_quoted(&self, start: usize, quote: u8) -> usize {
+        let backslash_escapes = quote != b'`'
+            && match self.db_type {
+                DatabaseType::MySQL => true,
+                DatabaseType::PostgreSQL => {
+                    start > 0 && matches!(self.bytes[start - 1], b'E' | b'e')
+                }
+                DatabaseType::SQLite => false,
+            };
+
+        let mut i = start + 1;
+        while i < self.bytes.len() {
+            match self.bytes[i] {
+                b'\\' if backslash_escapes => i += 2,
+                b if b == quote => {
+                    if self.bytes.get(i + 1) == Some(&quote) {
+                        i += 2;
+                    } else {
+                        return i + 1;
+                    }
+                }
+                _ => i += 1,
+            }
+        }
+        self.bytes.len()
+    }
+
+    fn skip_block_comment(&self, start: usize) -> usize {
+        let nested = self.db_type == DatabaseType::PostgreSQL;
+        let mut depth = 0;
+        let mut i = start;
+        while i < self.bytes.len() {
+            if self.bytes[i..].starts_with(b"/*") && (nested || depth == 0) {
+                depth += 1;
+                i += 2;
+            } else if self.bytes[i..].starts_with(b"*/") {
+                depth -= 1;
+                i += 2;
+                if depth == 0 {
+                    return i;
+                }
+            } else {
+                i += 1;
+            }
+        }
+        self.bytes.len()
+    }
+
+    /// Skips `$tag$ ... $tag$`. A `$` that does not open a tag (such as the
+    /// `$1` placeholder) is consumed on its own.
+    fn skip_dollar_quoted(&self, start: usize) -> usize {
+        let is_ident = |b: u8| b.is_ascii_alphanumeric() || b == b'_';
+        if start > 0 && is_ident(self.bytes[start - 1]) {
+            return start + 1;
+        }
+
+        let mut end = start + 1;
+        while end < self.bytes.len() && is_ident(self.bytes[end]) {
+            end += 1;
+        }
+        let starts_with_digit = self
+            .bytes
+            .get(start + 1)
+            .is_some_and(|b| b.is_ascii_digit());
+        if end >= self.bytes.len() || self.bytes[end] != b'$' || starts_with_digit {
+            return start + 1;
+        }
+
+        let tag = &self.bytes[start..=end];
+        let body = end + 1;
+        self.bytes[body..]
+            .windows(tag.len())
+            .position(|w| w == tag)
+            .map_or(self.bytes.len(), |p| body + p + tag.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DatabaseType::*;
+
+    /// Statement text with its first and last line.
+    type Expected<'a> = &'a [(&'a str, usize, usize)];
+
+    #[test]
+    fn splits_statements() {
+        let cases: &[(&str, DatabaseType, Expected)] = &[
+            ("", PostgreSQL, &[]),
+            (" ;; -- only a comment\n", PostgreSQL, &[]),
+            (
+                "SELECT 1;\nSELECT 2;",
+                PostgreSQL,
+                &[("SELECT 1", 1, 1), ("SELECT 2", 2, 2)],
+            ),
+            (
+                "SELECT 1\n\n\nSELECT 2;\nSELECT 3",
+                SQLite,
+                &[("SELECT 1\n\n\nSELECT 2", 1, 4), ("SELECT 3", 5, 5)],
+            ),
+            (
+                "SELECT 'a;b', \"c;d\"; SELECT 'it''s;'",
+                SQLite,
+                &[("SELECT 'a;b', \"c;d\"", 1, 1), ("SELECT 'it''s;'", 1, 1)],
+            ),
+            (
+                "SELECT 1 -- ; not here\n; /* ; */ SELECT 2",
+                PostgreSQL,
+                &[("SELECT 1 -- ; not here", 1, 1), ("/* ; */ SELECT 2", 2, 2)],
+            ),
+            // PostgreSQL block comments nest; MySQL ones do not.
+            (
+                "/* a /* b; */ c; */ SELECT 1; SELECT 2",
+                PostgreSQL,
+                &[("/* a /* b; */ c; */ SELECT 1", 1, 1), ("SELECT 2", 1, 1)],
+            ),
+            (
+                "/* a /* b */ SELECT 1; SELECT 2",
+                MySQL,
+                &[("/* a /* b */ SELECT 1", 1, 1), ("SELECT 2", 1, 1)],
+            ),
+            // Backslash escapes: always in MySQL, only in E'' in PostgreSQL.
+            (
+                "SELECT 'a\\';b'; SELECT 2",
+                MySQL,
+                &[("SELECT 'a\\';b'", 1, 1), ("SELECT 2", 1, 1)],
+            ),
+            (
+                "SELECT E'a\\';b'; SELECT 'c\\'; SELECT 2",
+                PostgreSQL,
+                &[
+                    ("SELECT E'a\\';b'", 1, 1),
+                    ("SELECT 'c\\'", 1, 1),
+                    ("SELECT 2", 1, 1),
+                ],
+            ),
+            (
+                "SELECT `a;b` FROM t # c;\n; SELECT 1--2;",
+                MySQL,
+                &[("SELECT `a;b` FROM t # c;", 1, 1), ("SELECT 1--2", 2, 2)],
+            ),
+            (
+                "CREATE FUNCTION f() RETURNS int AS $body$ SELECT 1; $body$ LANGUAGE sql;\nSELECT $1;",
+                PostgreSQL,
+                &[
+                    (
+                        "CREATE FUNCTION f() RETURNS int AS $body$ SELECT 1; $body$ LANGUAGE sql",
+                        1,
+                        1,
+                    ),
+                    ("SELECT $1", 2, 2),
+                ],
+            ),
+            (
+                "DO $$ BEGIN\n  PERFORM 1;\nEND $$;",
+                PostgreSQL,
+                &[("DO $$ BEGIN\n  PERFORM 1;\nEND $$", 1, 3)],
+            ),
+            (
+                "SELECT 1;\nDELIMITER //\nCREATE PROCEDURE p() BEGIN\n  SELECT 1;\nEND //\ndelimiter ;\nSELECT 2;",
+                MySQL,
+                &[
+                    ("SELECT 1", 1, 1),
+                    ("CREATE PROCEDURE p() BEGIN\n  SELECT 1;\nEND", 3, 5),
+                    ("SELECT 2", 7, 7),
+                ],
+            ),
+            // DELIMITER is only a directive for MySQL.
+            (
+                "DELIMITER //\nSELECT 1;",
+                SQLite,
+                &[("DELIMITER //\nSELECT 1", 1, 2)],
+            ),
+            ("SELECT 'unterminated; SELECT 2", SQLite, &[("SELECT 'unterminated; SELECT 2", 1, 1)]),
+        ];
+        for (sql, db_type, expected) in cases {
+            let actual: Vec<_> = split_statements(sql, *db_type)
+                .into_iter()
+                .map(|s| (s.sql, s.start_line, s.end_line))
+                .collect();
+            let expected: Vec<_> = expected
+                .iter()
+                .map(|&(sql, start, end)| (sql.to_string(), start, end))
+                .collect();
+            assert_eq!(actual, expected, "{:?}", sql);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use super::classify::allowed_read_only;
+use super::traits::{DatabaseType, DbError, DbResult};
+use sqlx::pool::PoolConnection;
+use sqlx::{Database, Executor, Pool};
+use std::ops::{Deref, DerefMut};
+use tokio::sync::{Mutex, MutexGuard};
+
+pub struct SessionPool<DB: Database> {
+    pool: Pool<DB>,
+    pinned: Mutex<Option<PoolConnection<DB>>>,
+    read_only: bool,
+}
+
+pub enum Session<'a, DB: Database> {
+    Pinned(MutexGuard<'a, Option<PoolConnection<DB>>>),
+    Pooled(PoolConnection<DB>),
+}
+
+impl<DB: Database> Deref for Session<'_, DB> {
+    type Target = DB::Connection;
+
+    fn deref(&self) -> &Self::Target {
+        match self {
+            Session::Pinned(guard) => guard.as_ref().expect("pinned session released"),
+            Session::Pooled(conn) => conn,
+        }
+    }
+}
+
+impl<DB: Database> DerefMut for Session<'_, DB> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        match self {
+            Session::Pinned(guard) => guard.as_mut().expect("pinned session released"),
+            Session::Pooled(conn) => conn,
+        }
+    }
+}
+
+impl<DB: Database> Session<'_, DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    /// Opens a transaction that `discard_scratch` always rolls back. Inside a
+    /// pinned transaction it becomes a savepoint so the user's work survives.
+    pub async fn begin_scratch(&mut self) -> DbResult<()> {
+        let sql = match self {
+            Session::Pinned(_) => "SAVEPOINT scratch",
+            Session::Pooled(_) => "BEGIN",
+        };
+        self.run(sql).await
+    }
+
+    pub async fn discard_scratch(&mut self) -> DbResult<()> {
+        match self {
+            Session::Pinned(_) => {
+                self.run("ROLLBACK TO SAVEPOINT scratch").await?;
+                self.run("RELEASE SAVEPOINT scratch").await
+            }
+            Session::Pooled(_) => self.run("ROLLBACK").await,
+        }
+    }
+
+    /// Rolls back a transaction that a script opened on a pooled connection
+    /// and did not finish, so the connection does not go back to the pool
+    /// mid-transaction. A pinned transaction is left for the user to end.
+    pub async fn rollback_unpinned(&mut self) {
+        if let Session::Pooled(conn) = self {
+            // Harmless when no transaction is open; SQLite's error about that
+            // is ignored.
+            let _ = conn.execute("ROLLBACK").await;
+        }
+    }
+
+    async fn run(&mut self, sql: &str) -> DbResult<()> {
+        (&mut **self)
+            .execute(sql)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(())
+    }
+}
+
+impl<DB: Database> SessionPool<DB>
+where
+    for<'c> &'c mut DB::Connection: Executor<'c, Database = DB>,
+{
+    pub fn new(pool: Pool<DB>, read_only: bool) -> Self {
+        Self {
+            pool,
+            pinned: Mutex::new(None),
+            read_only,
+        }
+    }
+
+    pub async fn acquire(&self) -> DbResult<Session<'_, DB>> {
+        let pinned = self.pinned.lock().await;
+        if pinned.is_some() {
+            return Ok(Session::Pinned(pinned));
+        }
+        drop(pinned);
+
+        let conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?;
+        Ok(Session::Pooled(conn))
+    }
+
+    /// Always takes a fresh pool connection, even while a transaction is
+    /// pinned. Used for out-of-band work such as cancelling a running query.
+    pub async fn acquire_unpinned(&self) -> DbResult<PoolConnection<DB>> {
+        self.pool
+            .acquire()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))
+    }
+
+    pub fn ensure_writable(&self) -> DbResult<()> {
+        if self.read_only {
+            return Err(DbError::InvalidOperation(
+                "Connection is read-only".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Refuses user SQL on a read-only connection unless it is known not to
+    /// write, including statements that would turn writes back on for the
+    /// session.
+    pub fn ensure_allowed(&self, sql: &str, db_type: DatabaseType) -> DbResult<()> {
+        if self.read_only && !allowed_read_only(sql, db_type) {
+            return Err(DbError::InvalidOperation(
+                "Connection is read-only".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Like `acquire`, but refuses up front on read-only connections so that
+    /// write helpers never reach the server.
+    pub async fn acquire_writable(&self) -> DbResult<Session<'_, DB>> {
+        self.ensure_writable()?;
+        self.acquire().await
+    }
+
+    pub async fn begin(&self, sql: &str) -> DbResult<()> {
+        let mut pinned = self.pinned.lock().await;
+        if pinned.is_some() {
+            return Err(DbError::InvalidOperation(
+                "A transaction is already in progress".to_string(),
+            ));
+        }
+
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?;
+        conn.execute(sql)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+
+        *pinned = Some(conn);
+        Ok(())
+    }
+
+    pub async fn end(&self, sql: &str) -> DbResult<()> {
+        let mut pinned = self.pinned.lock().await;
+        let Some(mut conn) = pinned.take() else {
+            return Err(DbError::InvalidOperation(
+                "No transaction in progress".to_string(),
+            ));
+        };
+
+        let result = conn.execute(sql).await;
+        if let Err(e) = result {
+            // The session state is unknown after a failed COMMIT/ROLLBACK, so
+            // the connection is closed instead of being returned to the pool.
+            let _ = conn.close().await;
+            return Err(DbError::Query(e.to_string()));
+        }
+        Ok(())
+    }
+
+    pub async fn in_transaction(&self) -> bool {
+        self.pinned.lock().await.is_some()
+    }
+
+    pub async fn close(&self) {
+        if let Some(mut conn) = self.pinned.lock().await.take() {
+            let _ = conn.execute("ROLLBACK").await;
+        }
+        self.pool.close().await;
+    }
+}
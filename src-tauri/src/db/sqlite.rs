@@ -1,32 +1,55 @@
+use super::cancel::RunningQueries;
+use super::classify::classify;
+use super::explain::{self, QueryPlan};
+use super::filter::compile_filters;
+use super::params::json_param;
+use super::script::{ScriptStatement, StatementResult};
+use super::session::SessionPool;
 use super::traits::*;
 use async_trait::async_trait;
-use sqlx::sqlite::{SqliteConnectOptions, SqlitePool, SqlitePoolOptions, SqliteRow};
-use sqlx::{Column, Row};
-use std::str::FromStr;
-use std::sync::atomic::{AtomicBool, Ordering};
+use futures::TryStreamExt;
+use sqlx::sqlite::{SqliteConnectOptions, SqlitePoolOptions, SqliteRow};
+use sqlx::{Column, Row, Sqlite, TypeInfo, ValueRef};
+use std::collections::HashMap;
+use std::ptr::NonNull;
 use std::time::{Duration, Instant};
 
+/// Raw connection handle kept only so `sqlite3_interrupt` can be called on it.
+struct InterruptHandle(NonNull<libsqlite3_sys::sqlite3>);
+
+// SAFETY: sqlite3_interrupt is the only call made through the handle, and
+// SQLite documents it as safe to call from other threads.
+unsafe impl Send for InterruptHandle {}
+unsafe impl Sync for InterruptHandle {}
+
 pub struct SqliteConnection {
-    pool: SqlitePool,
-    in_transaction: AtomicBool,
+    running: RunningQueries<InterruptHandle>,
+    session: SessionPool<Sqlite>,
 }
 
 impl SqliteConnection {
-    pub async fn new(path: &str) -> DbResult<Self> {
-        let options = SqliteConnectOptions::from_str(path)
-            .map_err(|e| DbError::Connection(e.to_string()))?
-            .create_if_missing(true);
+    pub async fn new(params: &ConnectionParams) -> DbResult<Self> {
+        let mut options = SqliteConnectOptions::new()
+            .filename(&params.database)
+            .create_if_missing(!params.read_only)
+            .read_only(params.read_only)
+            .with_regexp();
+        for (key, value) in &params.parameters {
+            if key != "connect_timeout" {
+                options = options.pragma(key.clone(), value.clone());
+            }
+        }
 
         let pool = SqlitePoolOptions::new()
             .max_connections(5)
-            .acquire_timeout(Duration::from_secs(10))
+            .acquire_timeout(params.connect_timeout()?)
             .connect_with(options)
             .await
             .map_err(|e| DbError::Connection(e.to_string()))?;
 
         Ok(Self {
-            pool,
-            in_transaction: AtomicBool::new(false),
+            running: RunningQueries::default(),
+            session: SessionPool::new(pool, params.read_only),
         })
     }
 
@@ -54,45 +77,46 @@ impl SqliteConnection {
         }
     }
 
-    fn build_where_clause(&self, filters: &Option<Vec<FilterCondition>>) -> String {
-        let Some(filters) = filters else {
-            return String::new();
-        };
-        if filters.is_empty() {
-            return String::new();
+    /// Matches one row by every column of its primary key. Key values are
+    /// bound after any already in `values`.
+    fn build_key_clause(
+        key: &HashMap<String, serde_json::Value>,
+        values: &mut Vec<ParamValue>,
+    ) -> DbResult<String> {
+        if key.is_empty() {
+            return Err(DbError::InvalidOperation(
+                "Rows can only be changed in tables with a primary key".to_string(),
+            ));
         }
-
-        let conditions: Vec<String> = filters
-            .iter()
-            .map(|f| match f.operator {
-                FilterOperator::Equals => {
-                    format!("\"{}\" = '{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::NotEquals => {
-                    format!("\"{}\" != '{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::Contains => {
-                    format!("\"{}\" LIKE '%{}%'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::StartsWith => {
-                    format!("\"{}\" LIKE '{}%'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::EndsWith => {
-                    format!("\"{}\" LIKE '%{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::GreaterThan => {
-                    format!("\"{}\" > '{}'", f.column, f.value.replace('\'', "''"))
-                }
-                FilterOperator::LessThan => {
-                    format!("\"{}\" < '{}'", f.column, f.value.replace('\'', "''"))
+        let mut columns: Vec<_> = key.iter().collect();
+        columns.sort_by(|a, b| a.0.cmp(b.0));
+        let conditions: Vec<String> = columns
+            .into_iter()
+            .map(|(col, val)| {
+                if val.is_null() {
+                    format!("{} IS NULL", Self::quote_column(col))
+                } else {
+                    values.push(json_param(val));
+                    format!("{} = ?", Self::quote_column(col))
                 }
-                FilterOperator::IsNull => format!("\"{}\" IS NULL", f.column),
-                FilterOperator::IsNotNull => format!("\"{}\" IS NOT NULL", f.column),
-                FilterOperator::Raw => format!("\"{}\" {}", f.column, f.value),
             })
             .collect();
+        Ok(conditions.join(" AND "))
+    }
 
-        format!("WHERE {}", conditions.join(" AND "))
+    fn quote_column(col: &str) -> String {
+        format!("\"{}\"", col.replace('"', "\"\""))
+    }
+
+    fn build_where_clause(
+        &self,
+        filters: &Option<FilterNode>,
+        columns: &[ColumnInfo],
+    ) -> DbResult<(String, Vec<ParamValue>)> {
+        match filters {
+            Some(filters) => compile_filters(filters, columns, DatabaseType::SQLite),
+            None => Ok((String::new(), Vec::new())),
+        }
     }
 
     fn build_order_clause(&self, sort: &Option<Vec<SortColumn>>) -> String {
@@ -116,6 +140,248 @@ impl SqliteConnection {
 
         format!("ORDER BY {}", orders.join(", "))
     }
+
+    fn row_to_json(row: &SqliteRow) -> Vec<serde_json::Value> {
+        (0..row.len())
+            .map(|index| Self::decode_value(row, index))
+            .collect()
+    }
+
+    /// Decodes a result cell from the storage class of the value itself,
+    /// since SQLite columns are not bound to one type. The declared column
+    /// type is only used to recover booleans.
+    fn decode_value(row: &SqliteRow, index: usize) -> serde_json::Value {
+        use serde_json::Value;
+        let storage_class = match row.try_get_raw(index) {
+            Ok(raw) if !raw.is_null() => raw.type_info().name().to_string(),
+            _ => return Value::Null,
+        };
+        let declared = row.columns()[index].type_info().name().to_uppercase();
+
+        match storage_class.as_str() {
+            "INTEGER" if declared.starts_with("BOOL") => row
+                .try_get_unchecked::<bool, _>(index)
+                .map(Value::from)
+                .unwrap_or(Value::Null),
+            "INTEGER" => row
+                .try_get_unchecked::<i64, _>(index)
+                .map(Value::from)
+                .unwrap_or(Value::Null),
+            "REAL" => row
+                .try_get_unchecked::<f64, _>(index)
+                .map(Value::from)
+                .unwrap_or(Value::Null),
+            "BLOB" => row
+                .try_get_unchecked::<Vec<u8>, _>(index)
+                .map(|v| Value::String(Self::format_blob(&v)))
+                .unwrap_or(Value::Null),
+            _ => row
+                .try_get_unchecked::<String, _>(index)
+                .map(Value::from)
+                .unwrap_or(Value::Null),
+        }
+    }
+
+    /// Formats bytes as a SQLite blob literal.
+    fn format_blob(bytes: &[u8]) -> String {
+        let hex: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+        format!("X'{}'", hex)
+    }
+
+    /// Column names, engine types and nullability as reported by the
+    /// prepared statement, so they are known even when no rows come back.
+    async fn describe_columns(
+        conn: &mut sqlx::SqliteConnection,
+        sql: &str,
+    ) -> Option<Vec<ResultColumn>> {
+        use sqlx::Executor;
+        let desc = (&mut *conn).describe(sql).await.ok()?;
+        let columns = desc
+            .columns()
+            .iter()
+            .enumerate()
+            .map(|(i, col)| ResultColumn::new(col.name(), col.type_info().name(), desc.nullable(i)))
+            .collect();
+        Some(columns)
+    }
+
+    fn row_columns(row: &SqliteRow) -> Vec<ResultColumn> {
+        row.columns()
+            .iter()
+            .map(|col| ResultColumn::new(col.name(), col.type_info().name(), None))
+            .collect()
+    }
+
+    async fn track_query(&self, conn: &mut sqlx::SqliteConnection, query_id: &str) -> DbResult<()> {
+        let handle = conn
+            .lock_handle()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?
+            .as_raw_handle();
+        self.running.register(query_id, InterruptHandle(handle));
+        Ok(())
+    }
+
+    async fn stream_rows(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        sql: &str,
+        batch_size: usize,
+        on_event: &mut (dyn FnMut(QueryEvent) + Send),
+    ) -> DbResult<()> {
+        let start = Instant::now();
+        let columns = Self::describe_columns(conn, sql).await.unwrap_or_default();
+        on_event(QueryEvent::Columns { columns });
+
+        let mut rows = sqlx::query(sql).fetch(&mut *conn);
+        let mut batch = Vec::with_capacity(batch_size);
+        let mut row_count = 0u64;
+        while let Some(row) = rows
+            .try_next()
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?
+        {
+            batch.push(Self::row_to_json(&row));
+            row_count += 1;
+            if batch.len() >= batch_size {
+                on_event(QueryEvent::Rows {
+                    rows: std::mem::replace(&mut batch, Vec::with_capacity(batch_size)),
+                });
+            }
+        }
+        if !batch.is_empty() {
+            on_event(QueryEvent::Rows { rows: batch });
+        }
+
+        on_event(QueryEvent::Done {
+            row_count,
+            execution_time_ms: start.elapsed().as_millis(),
+        });
+        Ok(())
+    }
+
+    fn bind_params<'q>(
+        mut query: sqlx::query::Query<'q, Sqlite, sqlx::sqlite::SqliteArguments<'q>>,
+        params: &'q [ParamValue],
+    ) -> sqlx::query::Query<'q, Sqlite, sqlx::sqlite::SqliteArguments<'q>> {
+        for param in params {
+            query = match param {
+                ParamValue::Null => query.bind(None::<String>),
+                ParamValue::Bool(v) => query.bind(*v),
+                ParamValue::Int(v) => query.bind(*v),
+                ParamValue::Float(v) => query.bind(*v),
+                ParamValue::Text(v) => query.bind(v.as_str()),
+                ParamValue::Json(v) => query.bind(sqlx::types::Json(v)),
+            };
+        }
+        query
+    }
+
+    async fn run_query(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        sql: &str,
+        params: &[ParamValue],
+        max_rows: Option<u64>,
+    ) -> DbResult<QueryResult> {
+        let start = Instant::now();
+        let info = classify(sql, DatabaseType::SQLite);
+        self.session.ensure_allowed(sql, DatabaseType::SQLite)?;
+
+        if info.returns_rows {
+            let described = Self::describe_columns(conn, sql).await;
+
+            // A read stops one row past the cap and drops the rest unread. A
+            // write runs to completion anyway, so its rows are still counted.
+            let count_all = info.kind.modifies_data();
+            let mut rows = Vec::new();
+            let mut row_count = 0u64;
+            let mut truncated = false;
+            let mut stream = Self::bind_params(sqlx::query(sql), params).fetch(&mut *conn);
+            while let Some(row) = stream
+                .try_next()
+                .await
+                .map_err(|e| DbError::Query(e.to_string()))?
+            {
+                if max_rows.is_some_and(|max| rows.len() as u64 >= max) {
+                    truncated = true;
+                    if !count_all {
+                        break;
+                    }
+                } else {
+                    rows.push(row);
+                }
+                row_count += 1;
+            }
+            drop(stream);
+
+            let execution_time_ms = start.elapsed().as_millis();
+
+            let columns = described
+                .or_else(|| rows.first().map(Self::row_columns))
+                .unwrap_or_default();
+
+            let result_rows: Vec<Vec<serde_json::Value>> =
+                rows.iter().map(Self::row_to_json).collect();
+
+            Ok(QueryResult {
+                columns,
+                rows: result_rows,
+                rows_affected: row_count,
+                execution_time_ms,
+                truncated,
+                messages: Vec::new(),
+            })
+        } else {
+            let result = Self::bind_params(sqlx::query(sql), params)
+                .execute(&mut *conn)
+                .await
+                .map_err(|e| DbError::Query(e.to_string()))?;
+
+            Ok(QueryResult {
+                columns: vec![],
+                rows: vec![],
+                rows_affected: result.rows_affected(),
+                execution_time_ms: start.elapsed().as_millis(),
+                truncated: false,
+                messages: Vec::new(),
+            })
+        }
+    }
+
+    /// Runs an ad-hoc statement under `limits`. SQLite has no statement
+    /// timeout, so a progress handler interrupts the statement once the
+    /// deadline has passed.
+    async fn run_limited(
+        &self,
+        conn: &mut sqlx::SqliteConnection,
+        sql: &str,
+        params: &[ParamValue],
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let Some(timeout) = limits.timeout_ms else {
+            return self.run_query(conn, sql, params, limits.max_rows).await;
+        };
+
+        let deadline = Instant::now() + Duration::from_millis(timeout);
+        conn.lock_handle()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?
+            .set_progress_handler(1000, move || Instant::now() < deadline);
+        let result = self.run_query(conn, sql, params, limits.max_rows).await;
+        conn.lock_handle()
+            .await
+            .map_err(|e| DbError::Connection(e.to_string()))?
+            .remove_progress_handler();
+
+        match result {
+            Err(DbError::Query(_)) if Instant::now() >= deadline => Err(DbError::Query(format!(
+                "Query exceeded the {} ms timeout",
+                timeout
+            ))),
+            result => result,
+        }
+    }
 }
 
 #[async_trait]
@@ -125,8 +391,9 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn test_connection(&self) -> DbResult<()> {
+        let mut conn = self.session.acquire().await?;
         sqlx::query("SELECT 1")
-            .fetch_one(&self.pool)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| DbError::Connection(e.to_string()))?;
         Ok(())
@@ -139,6 +406,7 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn get_tables(&self, _schema: &str) -> DbResult<Vec<TableInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(
             r#"
             SELECT name, type
@@ -147,7 +415,7 @@ impl DbConnection for SqliteConnection {
             ORDER BY type, name
             "#,
         )
-        .fetch_all(&self.pool)
+        .fetch_all(&mut *conn)
         .await
         .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -169,8 +437,9 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn get_columns(&self, _schema: &str, table: &str) -> DbResult<Vec<ColumnInfo>> {
+        let mut conn = self.session.acquire().await?;
         let rows = sqlx::query(&format!("PRAGMA table_info(\"{}\")", table))
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -184,6 +453,7 @@ impl DbConnection for SqliteConnection {
                     data_type: row.get("type"),
                     is_nullable: notnull == 0,
                     is_primary_key: pk > 0,
+                    primary_key_ordinal: (pk > 0).then_some(pk as u32),
                     default_value: row.get("dflt_value"),
                 }
             })
@@ -191,9 +461,10 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn get_indexes(&self, _schema: &str, table: &str) -> DbResult<Vec<IndexInfo>> {
+        let mut conn = self.session.acquire().await?;
         let query = format!("PRAGMA index_list(\"{}\")", table);
         let rows = sqlx::query(&query)
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -205,7 +476,7 @@ impl DbConnection for SqliteConnection {
 
             let col_query = format!("PRAGMA index_info(\"{}\")", index_name);
             let col_rows = sqlx::query(&col_query)
-                .fetch_all(&self.pool)
+                .fetch_all(&mut *conn)
                 .await
                 .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -224,16 +495,17 @@ impl DbConnection for SqliteConnection {
 
     async fn get_table_data(&self, params: FetchDataParams) -> DbResult<TableData> {
         let columns = self.get_columns(&params.schema, &params.table).await?;
+        let mut conn = self.session.acquire().await?;
 
-        let where_clause = self.build_where_clause(&params.filters);
+        let (where_clause, filter_values) = self.build_where_clause(&params.filters, &columns)?;
         let order_clause = self.build_order_clause(&params.sort);
 
         let count_query = format!(
             "SELECT COUNT(*) as count FROM \"{}\" {}",
             params.table, where_clause
         );
-        let count_row = sqlx::query(&count_query)
-            .fetch_one(&self.pool)
+        let count_row = Self::bind_params(sqlx::query(&count_query), &filter_values)
+            .fetch_one(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         let total_count: i64 = count_row.get("count");
@@ -242,8 +514,8 @@ impl DbConnection for SqliteConnection {
             "SELECT * FROM \"{}\" {} {} LIMIT {} OFFSET {}",
             params.table, where_clause, order_clause, params.limit, params.offset
         );
-        let rows = sqlx::query(&data_query)
-            .fetch_all(&self.pool)
+        let rows = Self::bind_params(sqlx::query(&data_query), &filter_values)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -264,78 +536,99 @@ impl DbConnection for SqliteConnection {
         })
     }
 
-    async fn execute_query(&self, sql: &str) -> DbResult<QueryResult> {
-        let start = Instant::now();
-        let sql_lower = sql.trim().to_lowercase();
-        let is_select = sql_lower.starts_with("select") || sql_lower.starts_with("with");
+    async fn execute_query(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        query_id: Option<&str>,
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let mut conn = self.session.acquire().await?;
+        let Some(query_id) = query_id else {
+            return self.run_limited(&mut conn, sql, params, limits).await;
+        };
 
-        if is_select {
-            use sqlx::Executor;
-            let describe = self.pool.describe(sql).await.ok();
+        self.track_query(&mut conn, query_id).await?;
+        let result = self.run_limited(&mut conn, sql, params, limits).await;
+        self.running.finish(query_id, result).await
+    }
 
-            let rows = sqlx::query(sql)
-                .fetch_all(&self.pool)
+    async fn execute_script(
+        &self,
+        statements: &[ScriptStatement],
+        stop_on_error: bool,
+    ) -> DbResult<Vec<StatementResult>> {
+        // Every statement runs on the same session so that SET, temporary
+        // tables and explicit BEGIN/COMMIT carry over between them.
+        let mut conn = self.session.acquire().await?;
+        let mut results = Vec::with_capacity(statements.len());
+        for statement in statements {
+            let outcome = self.run_query(&mut conn, &statement.sql, &[], None).await;
+            let failed = outcome.is_err();
+            results.push(StatementResult::new(statement, outcome));
+            if failed && stop_on_error {
+                break;
+            }
+        }
+        conn.rollback_unpinned().await;
+        Ok(results)
+    }
+
+    async fn explain_query(&self, sql: &str, analyze: bool) -> DbResult<QueryPlan> {
+        if analyze {
+            return Err(DbError::InvalidOperation(
+                "SQLite does not support EXPLAIN ANALYZE".to_string(),
+            ));
+        }
+        let sql = sql.trim().trim_end_matches(';');
+        let mut conn = self.session.acquire().await?;
+        let rows =
+            sqlx::query_as::<_, (i64, i64, i64, String)>(&format!("EXPLAIN QUERY PLAN {}", sql))
+                .fetch_all(&mut *conn)
                 .await
                 .map_err(|e| DbError::Query(e.to_string()))?;
 
-            let execution_time_ms = start.elapsed().as_millis();
-
-            let columns: Vec<String> = if !rows.is_empty() {
-                rows[0]
-                    .columns()
-                    .iter()
-                    .map(|c| c.name().to_string())
-                    .collect()
-            } else if let Some(desc) = describe {
-                desc.columns()
-                    .iter()
-                    .map(|c| c.name().to_string())
-                    .collect()
-            } else {
-                vec![]
-            };
+        Ok(explain::parse_sqlite(
+            rows.into_iter()
+                .map(|(id, parent, _, detail)| (id, parent, detail))
+                .collect(),
+        ))
+    }
 
-            let mut result_rows: Vec<Vec<serde_json::Value>> = Vec::new();
-            for row in &rows {
-                let mut row_data: Vec<serde_json::Value> = Vec::new();
-                for col in row.columns() {
-                    let value: serde_json::Value = row
-                        .try_get::<String, _>(col.name())
-                        .map(serde_json::Value::from)
-                        .or_else(|_| {
-                            row.try_get::<i64, _>(col.name())
-                                .map(serde_json::Value::from)
-                        })
-                        .or_else(|_| {
-                            row.try_get::<f64, _>(col.name())
-                                .map(serde_json::Value::from)
-                        })
-                        .unwrap_or(serde_json::Value::Null);
-                    row_data.push(value);
-                }
-                result_rows.push(row_data);
-            }
+    async fn dry_run(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult> {
+        let mut conn = self.session.acquire_writable().await?;
+        conn.begin_scratch().await?;
+        let outcome = self.run_limited(&mut conn, sql, params, limits).await;
+        conn.discard_scratch().await?;
+        outcome
+    }
 
-            let rows_affected = result_rows.len() as u64;
-            Ok(QueryResult {
-                columns,
-                rows: result_rows,
-                rows_affected,
-                execution_time_ms,
-            })
-        } else {
-            let result = sqlx::query(sql)
-                .execute(&self.pool)
-                .await
-                .map_err(|e| DbError::Query(e.to_string()))?;
+    async fn stream_query(
+        &self,
+        sql: &str,
+        query_id: &str,
+        batch_size: usize,
+        on_event: &mut (dyn FnMut(QueryEvent) + Send),
+    ) -> DbResult<()> {
+        self.session.ensure_allowed(sql, DatabaseType::SQLite)?;
+        let mut conn = self.session.acquire().await?;
+        self.track_query(&mut conn, query_id).await?;
+        let result = self.stream_rows(&mut conn, sql, batch_size, on_event).await;
+        self.running.finish(query_id, result).await
+    }
 
-            Ok(QueryResult {
-                columns: vec![],
-                rows: vec![],
-                rows_affected: result.rows_affected(),
-                execution_time_ms: start.elapsed().as_millis(),
-            })
-        }
+    async fn cancel_query(&self, query_id: &str) -> DbResult<()> {
+        // sqlite3_interrupt is safe to call from any thread, and the registry
+        // lock guarantees the connection is still running this query.
+        self.running.cancel(query_id, |handle| unsafe {
+            libsqlite3_sys::sqlite3_interrupt(handle.0.as_ptr())
+        })?;
+        Ok(())
     }
 
     async fn get_distinct_values(
@@ -345,6 +638,7 @@ impl DbConnection for SqliteConnection {
         column: &str,
         limit: Option<u32>,
     ) -> DbResult<Vec<serde_json::Value>> {
+        let mut conn = self.session.acquire().await?;
         let limit_clause = limit.map(|l| format!(" LIMIT {}", l)).unwrap_or_default();
         let sql = format!(
             "SELECT DISTINCT \"{}\" FROM \"{}\" WHERE \"{}\" IS NOT NULL ORDER BY \"{}\"{}",
@@ -352,7 +646,7 @@ impl DbConnection for SqliteConnection {
         );
 
         let rows = sqlx::query(&sql)
-            .fetch_all(&self.pool)
+            .fetch_all(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -367,45 +661,44 @@ impl DbConnection for SqliteConnection {
         Ok(values)
     }
 
+    async fn estimate_row_count(
+        &self,
+        _schema: Option<&str>,
+        table: &str,
+    ) -> DbResult<Option<u64>> {
+        // SQLite keeps no row statistics, so this is an exact count.
+        let mut conn = self.session.acquire().await?;
+        let count: i64 = sqlx::query_scalar(&format!("SELECT COUNT(*) FROM \"{}\"", table))
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(|e| DbError::Query(e.to_string()))?;
+        Ok(Some(count as u64))
+    }
+
     async fn update_row(&self, update: RowUpdate) -> DbResult<u64> {
-        let format_value = |v: &serde_json::Value| -> String {
-            if v.is_null() {
-                "NULL".to_string()
-            } else if v.is_number() {
-                v.to_string()
-            } else if v.is_boolean() {
-                if v.as_bool().unwrap() {
-                    "1".to_string()
-                } else {
-                    "0".to_string()
-                }
-            } else if v.is_string() {
-                let s = v.as_str().unwrap();
-                format!("'{}'", s.replace('\'', "''"))
-            } else {
-                let s = v.to_string();
-                format!("'{}'", s.replace('\'', "''"))
-            }
-        };
+        let mut conn = self.session.acquire_writable().await?;
 
+        let mut values = Vec::new();
         let set_clauses: Vec<String> = update
             .updates
             .iter()
-            .map(|(col, val)| format!("\"{}\" = {}", col, format_value(val)))
+            .map(|(col, val)| {
+                values.push(json_param(val));
+                format!("{} = ?", Self::quote_column(col))
+            })
             .collect();
 
-        let pk_formatted = format_value(&update.primary_key_value);
+        let key_clause = Self::build_key_clause(&update.primary_key, &mut values)?;
 
         let sql = format!(
-            "UPDATE \"{}\" SET {} WHERE \"{}\" = {}",
+            "UPDATE \"{}\" SET {} WHERE {}",
             update.table,
             set_clauses.join(", "),
-            update.primary_key_column,
-            pk_formatted
+            key_clause
         );
 
-        let result = sqlx::query(&sql)
-            .execute(&self.pool)
+        let result = Self::bind_params(sqlx::query(&sql), &values)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -413,6 +706,7 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn insert_row(&self, insert: RowInsert) -> DbResult<serde_json::Value> {
+        let mut conn = self.session.acquire_writable().await?;
         let columns: Vec<String> = insert.values.keys().map(|k| format!("\"{}\"", k)).collect();
 
         let values: Vec<String> = insert
@@ -447,7 +741,7 @@ impl DbConnection for SqliteConnection {
         );
 
         let result = sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -455,25 +749,14 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn delete_row(&self, delete: RowDelete) -> DbResult<u64> {
-        let pk_formatted = if delete.primary_key_value.is_null() {
-            "NULL".to_string()
-        } else if delete.primary_key_value.is_number() {
-            delete.primary_key_value.to_string()
-        } else if delete.primary_key_value.is_string() {
-            let s = delete.primary_key_value.as_str().unwrap();
-            format!("'{}'", s.replace('\'', "''"))
-        } else {
-            let s = delete.primary_key_value.to_string();
-            format!("'{}'", s.replace('\'', "''"))
-        };
+        let mut conn = self.session.acquire_writable().await?;
+        let mut values = Vec::new();
+        let key_clause = Self::build_key_clause(&delete.primary_key, &mut values)?;
 
-        let sql = format!(
-            "DELETE FROM \"{}\" WHERE \"{}\" = {}",
-            delete.table, delete.primary_key_column, pk_formatted
-        );
+        let sql = format!("DELETE FROM \"{}\" WHERE {}", delete.table, key_clause);
 
-        let result = sqlx::query(&sql)
-            .execute(&self.pool)
+        let result = Self::bind_params(sqlx::query(&sql), &values)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
 
@@ -493,15 +776,17 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn drop_table(&self, _schema: &str, table: &str, _cascade: bool) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         let sql = format!("DROP TABLE \"{}\"", table);
         sqlx::query(&sql)
-            .execute(&self.pool)
+            .execute(&mut *conn)
             .await
             .map_err(|e| DbError::Query(e.to_string()))?;
         Ok(())
     }
 
     async fn alter_table(&self, params: AlterTableParams) -> DbResult<()> {
+        let mut conn = self.session.acquire_writable().await?;
         for change in params.changes {
             match change.action {
                 ColumnChangeAction::Add => {
@@ -515,7 +800,7 @@ impl DbConnection for SqliteConnection {
                         params.table, change.column, data_type, default
                     );
                     sqlx::query(&sql)
-                        .execute(&self.pool)
+                        .execute(&mut *conn)
                         .await
                         .map_err(|e| DbError::Query(e.to_string()))?;
                 }
@@ -526,7 +811,7 @@ impl DbConnection for SqliteConnection {
                         params.table, change.column, new_name
                     );
                     sqlx::query(&sql)
-                        .execute(&self.pool)
+                        .execute(&mut *conn)
                         .await
                         .map_err(|e| DbError::Query(e.to_string()))?;
                 }
@@ -544,38 +829,23 @@ impl DbConnection for SqliteConnection {
     }
 
     async fn begin_transaction(&self) -> DbResult<()> {
-        sqlx::query("BEGIN TRANSACTION")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(true, Ordering::SeqCst);
-        Ok(())
+        self.session.begin("BEGIN TRANSACTION").await
     }
 
     async fn commit(&self) -> DbResult<()> {
-        sqlx::query("COMMIT")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(false, Ordering::SeqCst);
-        Ok(())
+        self.session.end("COMMIT").await
     }
 
     async fn rollback(&self) -> DbResult<()> {
-        sqlx::query("ROLLBACK")
-            .execute(&self.pool)
-            .await
-            .map_err(|e| DbError::Query(e.to_string()))?;
-        self.in_transaction.store(false, Ordering::SeqCst);
-        Ok(())
+        self.session.end("ROLLBACK").await
     }
 
     async fn in_transaction(&self) -> bool {
-        self.in_transaction.load(Ordering::SeqCst)
+        self.session.in_transaction().await
     }
 
     async fn close(&self) -> DbResult<()> {
-        self.pool.close().await;
+        self.session.close().await;
         Ok(())
     }
 }
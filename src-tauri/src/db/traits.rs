@@ -1,6 +1,10 @@
+use super::explain::QueryPlan;
+use super::script::{ScriptStatement, StatementResult};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SchemaInfo {
@@ -20,6 +24,8 @@ pub struct ColumnInfo {
     pub data_type: String,
     pub is_nullable: bool,
     pub is_primary_key: bool,
+    /// Position of the column within the primary key, starting at 1.
+    pub primary_key_ordinal: Option<u32>,
     pub default_value: Option<String>,
 }
 
@@ -38,12 +44,109 @@ pub struct TableData {
     pub total_count: i64,
 }
 
+/// Describes one column of an ad-hoc result. The source fields are only set
+/// when the driver reports which table column the value was read from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ResultColumn {
+    pub name: String,
+    pub type_name: String,
+    pub nullable: Option<bool>,
+    pub source_schema: Option<String>,
+    pub source_table: Option<String>,
+    pub source_column: Option<String>,
+}
+
+impl ResultColumn {
+    pub fn new(name: &str, type_name: &str, nullable: Option<bool>) -> Self {
+        Self {
+            name: name.to_string(),
+            type_name: type_name.to_string(),
+            nullable,
+            source_schema: None,
+            source_table: None,
+            source_column: None,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct QueryResult {
-    pub columns: Vec<String>,
+    pub columns: Vec<ResultColumn>,
     pub rows: Vec<Vec<serde_json::Value>>,
     pub rows_affected: u64,
     pub execution_time_ms: u128,
+    /// Set when `max_rows` cut the result short. A read then stops early and
+    /// `rows_affected` counts only the rows returned; a write still counts
+    /// every row it changed.
+    #[serde(default)]
+    pub truncated: bool,
+    #[serde(default)]
+    pub messages: Vec<QueryMessage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MessageSeverity {
+    Error,
+    Warning,
+    Notice,
+    Info,
+    Debug,
+}
+
+/// A notice or warning the server sent while running a statement.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QueryMessage {
+    pub severity: MessageSeverity,
+    /// The MySQL warning code. PostgreSQL notices arrive without one.
+    pub code: Option<String>,
+    pub text: String,
+}
+
+/// Per-execution guards for `execute_query`. The timeout is enforced by the
+/// server where it has one; `max_rows` stops reading once reached.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct QueryLimits {
+    pub timeout_ms: Option<u64>,
+    pub max_rows: Option<u64>,
+}
+
+/// A typed value bound to a query placeholder.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", content = "value", rename_all = "snake_case")]
+pub enum ParamValue {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Text(String),
+    Json(serde_json::Value),
+}
+
+/// Values for an ad-hoc query, either in placeholder order (`$1`, `?`) or
+/// keyed by the `:name` they replace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum QueryParams {
+    Positional(Vec<ParamValue>),
+    Named(HashMap<String, ParamValue>),
+}
+
+/// Emitted by `stream_query`: column names first, then row batches, then a
+/// final summary once the result set is exhausted.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum QueryEvent {
+    Columns {
+        columns: Vec<ResultColumn>,
+    },
+    Rows {
+        rows: Vec<Vec<serde_json::Value>>,
+    },
+    Done {
+        row_count: u64,
+        execution_time_ms: u128,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -59,11 +162,39 @@ pub enum SortDirection {
     Desc,
 }
 
+/// A node of the filter tree sent with `FetchDataParams`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FilterNode {
+    Group(FilterGroup),
+    Condition(FilterCondition),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FilterGroup {
+    pub combinator: FilterCombinator,
+    /// Wraps the whole group in `NOT`.
+    #[serde(default)]
+    pub negated: bool,
+    pub children: Vec<FilterNode>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FilterCombinator {
+    And,
+    Or,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FilterCondition {
     pub column: String,
     pub operator: FilterOperator,
+    #[serde(default)]
     pub value: String,
+    /// Operands of `In`, `NotIn` and `Between`.
+    #[serde(default)]
+    pub values: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -72,13 +203,28 @@ pub enum FilterOperator {
     Equals,
     NotEquals,
     Contains,
+    NotContains,
     StartsWith,
     EndsWith,
     GreaterThan,
+    GreaterThanOrEqual,
     LessThan,
+    LessThanOrEqual,
+    /// A pattern with the user's own `%` and `_` wildcards.
+    Like,
+    NotLike,
+    In,
+    NotIn,
+    Between,
+    Regex,
     IsNull,
     IsNotNull,
-    Raw,
+    /// NULL or an empty string.
+    IsEmpty,
+    /// Date-relative comparisons; the value is a span such as `7 days`.
+    WithinLast,
+    WithinNext,
+    OlderThan,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -88,15 +234,15 @@ pub struct FetchDataParams {
     pub limit: i64,
     pub offset: i64,
     pub sort: Option<Vec<SortColumn>>,
-    pub filters: Option<Vec<FilterCondition>>,
+    pub filters: Option<FilterNode>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RowUpdate {
     pub schema: String,
     pub table: String,
-    pub primary_key_column: String,
-    pub primary_key_value: serde_json::Value,
+    /// Value of every primary key column, identifying the row.
+    pub primary_key: HashMap<String, serde_json::Value>,
     pub updates: HashMap<String, serde_json::Value>,
 }
 
@@ -111,8 +257,7 @@ pub struct RowInsert {
 pub struct RowDelete {
     pub schema: String,
     pub table: String,
-    pub primary_key_column: String,
-    pub primary_key_value: serde_json::Value,
+    pub primary_key: HashMap<String, serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -159,6 +304,53 @@ impl std::fmt::Display for DatabaseType {
     }
 }
 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SslMode {
+    Disable,
+    #[default]
+    Prefer,
+    Require,
+    VerifyCa,
+    VerifyFull,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TlsConfig {
+    #[serde(default)]
+    pub ssl_mode: SslMode,
+    pub ca_cert_path: Option<String>,
+    pub client_cert_path: Option<String>,
+    pub client_key_path: Option<String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ConnectionParams {
+    pub host: String,
+    pub port: u16,
+    pub database: String,
+    pub username: String,
+    pub password: String,
+    pub tls: TlsConfig,
+    pub parameters: HashMap<String, String>,
+    pub read_only: bool,
+    /// Unix socket to connect through instead of TCP to `host`. `host` is
+    /// still what TLS verifies the server certificate against.
+    pub socket: Option<PathBuf>,
+}
+
+impl ConnectionParams {
+    pub fn connect_timeout(&self) -> DbResult<Duration> {
+        match self.parameters.get("connect_timeout") {
+            Some(value) => value
+                .parse::<u64>()
+                .map(Duration::from_secs)
+                .map_err(|_| DbError::Connection(format!("Invalid connect_timeout: {}", value))),
+            None => Ok(Duration::from_secs(10)),
+        }
+    }
+}
+
 pub type DbResult<T> = Result<T, DbError>;
 
 #[derive(Debug, thiserror::Error)]
@@ -171,6 +363,16 @@ pub enum DbError {
     NotFound(String),
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+    #[error("SSH tunnel error: {0}")]
+    Tunnel(String),
+    #[error("SSH tunnel error: unknown host key {fingerprint} for {host}:{port}")]
+    UnknownHostKey {
+        host: String,
+        port: u16,
+        fingerprint: String,
+    },
+    #[error("Query was cancelled")]
+    Cancelled,
 }
 
 impl Serialize for DbError {
@@ -206,7 +408,44 @@ pub trait DbConnection: Send + Sync {
         limit: Option<u32>,
     ) -> DbResult<Vec<serde_json::Value>>;
 
-    async fn execute_query(&self, sql: &str) -> DbResult<QueryResult>;
+    /// A cheap row count for a table, taken from planner statistics where the
+    /// backend keeps them. `None` when no estimate is available.
+    async fn estimate_row_count(&self, schema: Option<&str>, table: &str) -> DbResult<Option<u64>>;
+
+    async fn execute_query(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        query_id: Option<&str>,
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult>;
+
+    async fn execute_script(
+        &self,
+        statements: &[ScriptStatement],
+        stop_on_error: bool,
+    ) -> DbResult<Vec<StatementResult>>;
+
+    async fn explain_query(&self, sql: &str, analyze: bool) -> DbResult<QueryPlan>;
+
+    /// Runs a write statement and rolls it back. Inside an open transaction
+    /// only a savepoint is rolled back, so the user's own work is kept.
+    async fn dry_run(
+        &self,
+        sql: &str,
+        params: &[ParamValue],
+        limits: QueryLimits,
+    ) -> DbResult<QueryResult>;
+
+    async fn stream_query(
+        &self,
+        sql: &str,
+        query_id: &str,
+        batch_size: usize,
+        on_event: &mut (dyn FnMut(QueryEvent) + Send),
+    ) -> DbResult<()>;
+
+    async fn cancel_query(&self, query_id: &str) -> DbResult<()>;
 
     async fn update_row(&self, update: RowUpdate) -> DbResult<u64>;
 
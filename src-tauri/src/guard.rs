@@ -0,0 +1,45 @@
+use crate::db::{DestructiveKind, TableRef};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use uuid::Uuid;
+
+/// Returned instead of running a destructive statement on a production
+/// connection. Sending `confirmation_token` back with the same request runs it.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfirmationRequired {
+    pub kind: DestructiveKind,
+    pub statement: String,
+    pub tables: Vec<TableRef>,
+    /// Rows in the affected tables; `None` when no table had an estimate.
+    pub estimated_rows: Option<u64>,
+    pub confirmation_token: String,
+}
+
+/// Hands out single-use tokens, each bound to one statement on one
+/// connection, so a confirmation cannot be replayed for something else.
+#[derive(Default)]
+pub struct ConfirmationGuard {
+    pending: Mutex<HashMap<String, (String, String)>>,
+}
+
+impl ConfirmationGuard {
+    pub fn issue(&self, connection_id: &str, statement: &str) -> String {
+        let token = Uuid::new_v4().to_string();
+        self.pending.lock().unwrap().insert(
+            token.clone(),
+            (connection_id.to_string(), statement.to_string()),
+        );
+        token
+    }
+
+    /// Consumes `token` and reports whether it was issued for exactly this
+    /// statement on this connection.
+    pub fn confirm(&self, token: &str, connection_id: &str, statement: &str) -> bool {
+        self.pending
+            .lock()
+            .unwrap()
+            .remove(token)
+            .is_some_and(|(id, sql)| id == connection_id && sql == statement)
+    }
+}
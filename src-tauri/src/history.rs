@@ -0,0 +1,210 @@
+use serde::{Deserialize, Serialize};
+use sqlx::sqlite::{SqliteConnectOptions, SqliteJournalMode, SqlitePool, SqlitePoolOptions};
+use sqlx::{QueryBuilder, Row, Sqlite};
+use std::path::Path;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryStatus {
+    Success,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub connection_id: String,
+    pub sql: String,
+    /// Unix timestamp in milliseconds.
+    pub executed_at: i64,
+    pub execution_time_ms: i64,
+    pub row_count: Option<i64>,
+    pub error: Option<String>,
+    pub status: HistoryStatus,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct HistoryFilter {
+    pub connection_id: Option<String>,
+    pub search: Option<String>,
+    pub status: Option<HistoryStatus>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+    pub limit: Option<u32>,
+    pub offset: Option<u32>,
+}
+
+/// Selects what `prune` deletes. Entries must match every option given; with
+/// neither `older_than` nor `keep_latest` set, all entries in scope go.
+#[derive(Debug, Default, Deserialize)]
+pub struct PruneOptions {
+    pub connection_id: Option<String>,
+    pub older_than: Option<i64>,
+    pub keep_latest: Option<u32>,
+}
+
+/// Query history kept in a SQLite database in the app data directory, so it
+/// outlives the webview's local storage and is shared by every window.
+pub struct HistoryStore {
+    pool: SqlitePool,
+}
+
+impl HistoryStore {
+    pub async fn open(path: &Path) -> Result<Self, sqlx::Error> {
+        let options = SqliteConnectOptions::new()
+            .filename(path)
+            .create_if_missing(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(4)
+            .connect_with(options)
+            .await?;
+
+        sqlx::query(
+            "CREATE TABLE IF NOT EXISTS query_history (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                connection_id TEXT NOT NULL,
+                sql TEXT NOT NULL,
+                executed_at INTEGER NOT NULL,
+                execution_time_ms INTEGER NOT NULL,
+                row_count INTEGER,
+                error TEXT
+            )",
+        )
+        .execute(&pool)
+        .await?;
+        sqlx::query(
+            "CREATE INDEX IF NOT EXISTS query_history_connection
+             ON query_history (connection_id, executed_at)",
+        )
+        .execute(&pool)
+        .await?;
+
+        Ok(Self { pool })
+    }
+
+    pub async fn record(
+        &self,
+        connection_id: &str,
+        sql: &str,
+        execution_time_ms: i64,
+        row_count: Option<i64>,
+        error: Option<&str>,
+    ) -> Result<(), sqlx::Error> {
+        sqlx::query(
+            "INSERT INTO query_history
+             (connection_id, sql, executed_at, execution_time_ms, row_count, error)
+             VALUES (?, ?, ?, ?, ?, ?)",
+        )
+        .bind(connection_id)
+        .bind(sql.trim())
+        .bind(chrono::Utc::now().timestamp_millis())
+        .bind(execution_time_ms)
+        .bind(row_count)
+        .bind(error)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    /// Returns matching entries, newest first.
+    pub async fn search(&self, filter: &HistoryFilter) -> Result<Vec<HistoryEntry>, sqlx::Error> {
+        let mut builder = QueryBuilder::<Sqlite>::new(
+            "SELECT id, connection_id, sql, executed_at, execution_time_ms, row_count, error
+             FROM query_history WHERE 1 = 1",
+        );
+        if let Some(connection_id) = &filter.connection_id {
+            builder
+                .push(" AND connection_id = ")
+                .push_bind(connection_id.clone());
+        }
+        if let Some(search) = filter.search.as_deref().filter(|s| !s.trim().is_empty()) {
+            let escaped = search
+                .trim()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_");
+            builder
+                .push(" AND sql LIKE ")
+                .push_bind(format!("%{}%", escaped))
+                .push(" ESCAPE '\\'");
+        }
+        match filter.status {
+            Some(HistoryStatus::Success) => {
+                builder.push(" AND error IS NULL");
+            }
+            Some(HistoryStatus::Error) => {
+                builder.push(" AND error IS NOT NULL");
+            }
+            None => {}
+        }
+        if let Some(since) = filter.since {
+            builder.push(" AND executed_at >= ").push_bind(since);
+        }
+        if let Some(until) = filter.until {
+            builder.push(" AND executed_at < ").push_bind(until);
+        }
+        builder
+            .push(" ORDER BY executed_at DESC, id DESC LIMIT ")
+            .push_bind(filter.limit.unwrap_or(100) as i64)
+            .push(" OFFSET ")
+            .push_bind(filter.offset.unwrap_or(0) as i64);
+
+        let rows = builder.build().fetch_all(&self.pool).await?;
+        rows.iter()
+            .map(|row| {
+                let error: Option<String> = row.try_get("error")?;
+                Ok(HistoryEntry {
+                    id: row.try_get("id")?,
+                    connection_id: row.try_get("connection_id")?,
+                    sql: row.try_get("sql")?,
+                    executed_at: row.try_get("executed_at")?,
+                    execution_time_ms: row.try_get("execution_time_ms")?,
+                    row_count: row.try_get("row_count")?,
+                    status: if error.is_some() {
+                        HistoryStatus::Error
+                    } else {
+                        HistoryStatus::Success
+                    },
+                    error,
+                })
+            })
+            .collect()
+    }
+
+    /// Deletes entries selected by `options` and returns how many went.
+    pub async fn prune(&self, options: &PruneOptions) -> Result<u64, sqlx::Error> {
+        let mut builder = QueryBuilder::<Sqlite>::new("DELETE FROM query_history WHERE 1 = 1");
+        if let Some(connection_id) = &options.connection_id {
+            builder
+                .push(" AND connection_id = ")
+                .push_bind(connection_id.clone());
+        }
+        if let Some(older_than) = options.older_than {
+            builder.push(" AND executed_at < ").push_bind(older_than);
+        }
+        if let Some(keep_latest) = options.keep_latest {
+            builder.push(" AND id NOT IN (SELECT id FROM query_history");
+            if let Some(connection_id) = &options.connection_id {
+                builder
+                    .push(" WHERE connection_id = ")
+                    .push_bind(connection_id.clone());
+            }
+            builder
+                .push(" ORDER BY executed_at DESC, id DESC LIMIT ")
+                .push_bind(keep_latest as i64)
+                .push(")");
+        }
+
+        let result = builder.build().execute(&self.pool).await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<(), sqlx::Error> {
+        sqlx::query("DELETE FROM query_history WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+}
@@ -1,14 +1,26 @@
 mod commands;
 mod connection;
 mod db;
+mod guard;
+mod history;
+mod import;
+mod secrets;
+mod snippets;
+mod tunnel;
 
 use commands::{
-    alter_table, begin_transaction, commit_transaction, connect_to_database, create_schema,
-    delete_connection, delete_row, disconnect_from_database, drop_schema, drop_table,
-    execute_query, export_data, get_columns, get_connections, get_distinct_values, get_indexes,
-    get_schemas, get_table_data, get_tables, get_transaction_status, insert_row,
-    rollback_transaction, save_connection, test_connection, update_row, AppState,
+    alter_table, begin_transaction, cancel_query, commit_transaction, connect_to_database,
+    create_schema, delete_connection, delete_history_entry, delete_row, delete_snippet,
+    disconnect_from_database, drop_schema, drop_table, dry_run_query, duplicate_connection,
+    execute_query, execute_script, execute_snippet, explain_query, export_data, export_snippets,
+    get_columns, get_connections, get_distinct_values, get_indexes, get_schemas, get_snippets,
+    get_table_data, get_tables, get_transaction_status, get_vault_status, import_connections,
+    import_snippets, insert_row, prune_query_history, rollback_transaction, save_connection,
+    save_snippet, search_query_history, stream_query, test_connection, trust_host_key,
+    unlock_vault, update_connection, update_row, update_snippet, AppState,
 };
+use history::HistoryStore;
+use secrets::VaultFiles;
 use tauri::Manager;
 
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
@@ -26,6 +38,15 @@ pub fn run() {
             app.handle()
                 .plugin(tauri_plugin_stronghold::Builder::with_argon2(&salt_path).build())?;
 
+            let vault_files = VaultFiles {
+                vault: app_data_dir.join("vault.hold"),
+                salt: salt_path,
+            };
+            let history = tauri::async_runtime::block_on(HistoryStore::open(
+                &app_data_dir.join("history.db"),
+            ))?;
+            app.manage(AppState::new(vault_files, history));
+
             #[cfg(debug_assertions)]
             {
                 if let Some(window) = app.get_webview_window("main") {
@@ -37,13 +58,18 @@ pub fn run() {
         })
         .plugin(tauri_plugin_opener::init())
         .plugin(tauri_plugin_dialog::init())
-        .manage(AppState::default())
         .invoke_handler(tauri::generate_handler![
+            get_vault_status,
+            unlock_vault,
             get_connections,
             save_connection,
+            update_connection,
+            duplicate_connection,
             delete_connection,
+            import_connections,
             test_connection,
             connect_to_database,
+            trust_host_key,
             disconnect_from_database,
             get_schemas,
             get_tables,
@@ -52,6 +78,21 @@ pub fn run() {
             get_table_data,
             get_distinct_values,
             execute_query,
+            execute_script,
+            explain_query,
+            dry_run_query,
+            search_query_history,
+            prune_query_history,
+            delete_history_entry,
+            get_snippets,
+            save_snippet,
+            update_snippet,
+            delete_snippet,
+            import_snippets,
+            export_snippets,
+            execute_snippet,
+            stream_query,
+            cancel_query,
             update_row,
             insert_row,
             delete_row,
@@ -0,0 +1,79 @@
+use serde::Deserialize;
+use std::path::PathBuf;
+use tauri_plugin_stronghold::kdf::KeyDerivation;
+use tauri_plugin_stronghold::stronghold::{Result, Stronghold};
+
+const CLIENT_NAME: &[u8] = b"db_gui_client";
+
+/// The vault snapshot and the salt its key is derived with.
+#[derive(Debug, Clone)]
+pub struct VaultFiles {
+    pub vault: PathBuf,
+    pub salt: PathBuf,
+}
+
+impl VaultFiles {
+    pub fn exists(&self) -> bool {
+        self.vault.exists()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SecretKind {
+    Password,
+    SshPassword,
+    SshPassphrase,
+}
+
+impl SecretKind {
+    fn key(self, connection_id: &str) -> Vec<u8> {
+        let prefix = match self {
+            SecretKind::Password => "password",
+            SecretKind::SshPassword => "ssh_password",
+            SecretKind::SshPassphrase => "ssh_passphrase",
+        };
+        format!("{}_{}", prefix, connection_id).into_bytes()
+    }
+}
+
+pub struct SecretStore {
+    stronghold: Stronghold,
+}
+
+impl SecretStore {
+    /// Opens the vault with a key derived from the user's master password,
+    /// creating it if it does not exist yet.
+    pub fn open(files: &VaultFiles, master_password: &str) -> Result<Self> {
+        let key = KeyDerivation::argon2(master_password, &files.salt);
+        let stronghold = Stronghold::new(&files.vault, key)?;
+        if stronghold.load_client(CLIENT_NAME).is_err() {
+            stronghold.create_client(CLIENT_NAME)?;
+        }
+        Ok(Self { stronghold })
+    }
+
+    pub fn get(&self, connection_id: &str, kind: SecretKind) -> Result<Option<String>> {
+        let client = self.stronghold.get_client(CLIENT_NAME)?;
+        let data = client.store().get(&kind.key(connection_id))?;
+        Ok(data.map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    pub fn set(&self, connection_id: &str, kind: SecretKind, secret: &str) -> Result<()> {
+        let client = self.stronghold.get_client(CLIENT_NAME)?;
+        client
+            .store()
+            .insert(kind.key(connection_id), secret.as_bytes().to_vec(), None)?;
+        Ok(())
+    }
+
+    pub fn remove(&self, connection_id: &str, kind: SecretKind) -> Result<()> {
+        let client = self.stronghold.get_client(CLIENT_NAME)?;
+        client.store().delete(&kind.key(connection_id))?;
+        Ok(())
+    }
+
+    pub fn save(&self) -> Result<()> {
+        self.stronghold.save()
+    }
+}
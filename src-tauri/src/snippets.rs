@@ -0,0 +1,403 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snippet {
+    pub id: String,
+    pub name: String,
+    pub sql: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Slash-separated folder path; `None` keeps the snippet at the top level.
+    #[serde(default)]
+    pub folder: Option<String>,
+    /// Connection the snippet belongs to; `None` makes it global.
+    #[serde(default)]
+    pub connection_id: Option<String>,
+    #[serde(default)]
+    pub updated_at: i64,
+}
+
+impl Snippet {
+    pub fn new(name: String, sql: String) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            name,
+            sql,
+            description: None,
+            tags: Vec::new(),
+            folder: None,
+            connection_id: None,
+            updated_at: chrono::Utc::now().timestamp_millis(),
+        }
+    }
+
+    /// Substitutes every `{{name}}` placeholder with its value. Values are
+    /// inserted as written, so they may be literals, identifiers or clauses.
+    pub fn render(&self, values: &HashMap<String, String>) -> Result<String, SnippetError> {
+        let mut rendered = String::with_capacity(self.sql.len());
+        let mut rest = self.sql.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(len) = rest[start + 2..].find("}}") else {
+                break;
+            };
+            let name = rest[start + 2..start + 2 + len].trim();
+            let value = values
+                .get(name)
+                .ok_or_else(|| SnippetError::MissingValue(name.to_string()))?;
+            rendered.push_str(&rest[..start]);
+            rendered.push_str(value);
+            rest = &rest[start + 4 + len..];
+        }
+        rendered.push_str(rest);
+        Ok(rendered)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum SnippetError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Invalid snippet file: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("Snippet not found: {0}")]
+    NotFound(String),
+    #[error("Missing value for {{{{{0}}}}}")]
+    MissingValue(String),
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct SnippetsFile {
+    snippets: Vec<Snippet>,
+}
+
+pub struct SnippetStore {
+    pub snippets: Vec<Snippet>,
+}
+
+impl SnippetStore {
+    pub fn load() -> Self {
+        let path = Self::config_path();
+        let file: SnippetsFile = if path.exists() {
+            let content = std::fs::read_to_string(&path).unwrap_or_default();
+            serde_json::from_str(&content).unwrap_or_default()
+        } else {
+            SnippetsFile::default()
+        };
+        Self {
+            snippets: file.snippets,
+        }
+    }
+
+    pub fn save(&self) -> Result<(), SnippetError> {
+        let path = Self::config_path();
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        Self::write_json(&path, &self.snippets)
+    }
+
+    /// Global snippets plus those belonging to `connection_id`, or every
+    /// snippet when no connection is given.
+    pub fn list(&self, connection_id: Option<&str>) -> Vec<Snippet> {
+        self.snippets
+            .iter()
+            .filter(|s| match (connection_id, s.connection_id.as_deref()) {
+                (Some(wanted), Some(owner)) => wanted == owner,
+                _ => true,
+            })
+            .cloned()
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Snippet> {
+        self.snippets.iter().find(|s| s.id == id)
+    }
+
+    pub fn add(&mut self, snippet: Snippet) {
+        self.snippets.push(snippet);
+    }
+
+    pub fn update(&mut self, mut snippet: Snippet) -> Result<(), SnippetError> {
+        let existing = self
+            .snippets
+            .iter_mut()
+            .find(|s| s.id == snippet.id)
+            .ok_or_else(|| SnippetError::NotFound(snippet.id.clone()))?;
+        snippet.updated_at = chrono::Utc::now().timestamp_millis();
+        *existing = snippet;
+        Ok(())
+    }
+
+    pub fn remove(&mut self, id: &str) {
+        self.snippets.retain(|s| s.id != id);
+    }
+
+    /// Imports a JSON collection or a directory of `.sql` files. Imported
+    /// snippets always get new ids, so importing twice yields copies.
+    pub fn import(&mut self, path: &Path) -> Result<Vec<String>, SnippetError> {
+        let mut imported = if path.is_dir() {
+            let mut snippets = Vec::new();
+            Self::read_sql_dir(path, None, &mut snippets)?;
+            snippets
+        } else {
+            let content = std::fs::read_to_string(path)?;
+            serde_json::from_str::<SnippetsFile>(&content)?.snippets
+        };
+
+        let mut ids = Vec::with_capacity(imported.len());
+        for snippet in &mut imported {
+            snippet.id = Uuid::new_v4().to_string();
+            ids.push(snippet.id.clone());
+        }
+        self.snippets.extend(imported);
+        Ok(ids)
+    }
+
+    /// Writes the selected snippets (all when `ids` is `None`) as a JSON
+    /// collection, or as `.sql` files laid out by folder when `as_sql_dir`.
+    pub fn export(
+        &self,
+        path: &Path,
+        ids: Option<&[String]>,
+        as_sql_dir: bool,
+    ) -> Result<usize, SnippetError> {
+        let selected: Vec<Snippet> = self
+            .snippets
+            .iter()
+            .filter(|s| ids.is_none_or(|ids| ids.contains(&s.id)))
+            .cloned()
+            .collect();
+
+        if !as_sql_dir {
+            Self::write_json(path, &selected)?;
+            return Ok(selected.len());
+        }
+
+        let mut used: Vec<PathBuf> = Vec::new();
+        for snippet in &selected {
+            let mut dir = path.to_path_buf();
+            for part in snippet.folder.iter().flat_map(|f| f.split('/')) {
+                if !part.is_empty() {
+                    dir.push(Self::file_stem(part));
+                }
+            }
+            std::fs::create_dir_all(&dir)?;
+
+            let stem = Self::file_stem(&snippet.name);
+            let mut file = dir.join(format!("{}.sql", stem));
+            let mut n = 2;
+            while used.contains(&file) {
+                file = dir.join(format!("{} ({}).sql", stem, n));
+                n += 1;
+            }
+            std::fs::write(&file, Self::to_sql_file(snippet))?;
+            used.push(file);
+        }
+        Ok(selected.len())
+    }
+
+    fn write_json(path: &Path, snippets: &[Snippet]) -> Result<(), SnippetError> {
+        let file = SnippetsFile {
+            snippets: snippets.to_vec(),
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&file)?)?;
+        Ok(())
+    }
+
+    fn file_stem(name: &str) -> String {
+        let stem: String = name
+            .chars()
+            .map(|c| match c {
+                '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+                c => c,
+            })
+            .collect();
+        match stem.trim() {
+            "" | "." | ".." => "snippet".to_string(),
+            stem => stem.to_string(),
+        }
+    }
+
+    /// Metadata goes into leading `-- key: value` comments so the file stays
+    /// runnable on its own.
+    fn to_sql_file(snippet: &Snippet) -> String {
+        let mut out = format!("-- name: {}\n", snippet.name);
+        if let Some(description) = &snippet.description {
+            for line in description.lines() {
+                out.push_str(&format!("-- description: {}\n", line));
+            }
+        }
+        if !snippet.tags.is_empty() {
+            out.push_str(&format!("-- tags: {}\n", snippet.tags.join(", ")));
+        }
+        out.push('\n');
+        out.push_str(snippet.sql.trim_end());
+        out.push('\n');
+        out
+    }
+
+    fn from_sql_file(stem: &str, folder: Option<String>, content: &str) -> Snippet {
+        let mut snippet = Snippet::new(stem.to_string(), String::new());
+        snippet.folder = folder;
+
+        let mut description: Vec<&str> = Vec::new();
+        let mut body = content;
+        loop {
+            let (line, rest) = body.split_once('\n').unwrap_or((body, ""));
+            let Some((key, value)) = line
+                .strip_prefix("--")
+                .and_then(|l| l.split_once(':'))
+                .map(|(k, v)| (k.trim(), v.trim()))
+            else {
+                break;
+            };
+            match key {
+                "name" => snippet.name = value.to_string(),
+                "description" => description.push(value),
+                "tags" => {
+                    snippet.tags = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|t| !t.is_empty())
+                        .map(str::to_string)
+                        .collect()
+                }
+                _ => break,
+            }
+            body = rest;
+        }
+
+        if !description.is_empty() {
+            snippet.description = Some(description.join("\n"));
+        }
+        snippet.sql = body.trim().to_string();
+        snippet
+    }
+
+    fn read_sql_dir(
+        dir: &Path,
+        folder: Option<&str>,
+        snippets: &mut Vec<Snippet>,
+    ) -> Result<(), SnippetError> {
+        let mut entries: Vec<_> = std::fs::read_dir(dir)?.collect::<Result<_, _>>()?;
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if path.is_dir() {
+                let sub = match folder {
+                    Some(folder) => format!("{}/{}", folder, name),
+                    None => name,
+                };
+                Self::read_sql_dir(&path, Some(&sub), snippets)?;
+            } else if let Some(stem) = name.strip_suffix(".sql") {
+                let content = std::fs::read_to_string(&path)?;
+                snippets.push(Self::from_sql_file(
+                    stem,
+                    folder.map(str::to_string),
+                    &content,
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    fn config_path() -> PathBuf {
+        dirs::config_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("db_gui")
+            .join("snippets.json")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_placeholders() {
+        let values: HashMap<String, String> = [("table", "users"), ("id", "42"), ("empty", "")]
+            .into_iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        let cases = [
+            ("SELECT 1", Ok("SELECT 1")),
+            (
+                "SELECT * FROM {{table}} WHERE id = {{ id }}",
+                Ok("SELECT * FROM users WHERE id = 42"),
+            ),
+            ("{{id}}{{id}}", Ok("4242")),
+            ("SELECT '{{empty}}'", Ok("SELECT ''")),
+            // An opening brace pair without a closing one is left alone.
+            ("SELECT '{{'", Ok("SELECT '{{'")),
+            ("SELECT {{id}}, '{{'", Ok("SELECT 42, '{{'")),
+            ("SELECT {{ missing }}", Err("missing")),
+        ];
+        for (sql, expected) in cases {
+            let snippet = Snippet::new("test".to_string(), sql.to_string());
+            let actual = match snippet.render(&values) {
+                Ok(rendered) => Ok(rendered),
+                Err(SnippetError::MissingValue(name)) => Err(name),
+                Err(e) => panic!("{}: {}", sql, e),
+            };
+            let expected = expected.map(str::to_string).map_err(str::to_string);
+            assert_eq!(actual, expected, "{}", sql);
+        }
+    }
+
+    #[test]
+    fn round_trips_sql_files() {
+        let mut snippet = Snippet::new(
+            "Active users".to_string(),
+            "-- not metadata\nSELECT * FROM users\n".to_string(),
+        );
+        snippet.description = Some("Users seen recently.\nExcludes bots.".to_string());
+        snippet.tags = vec!["users".to_string(), "daily".to_string()];
+
+        let content = SnippetStore::to_sql_file(&snippet);
+        assert_eq!(
+            content,
+            "-- name: Active users\n\
+             -- description: Users seen recently.\n\
+             -- description: Excludes bots.\n\
+             -- tags: users, daily\n\
+             \n\
+             -- not metadata\n\
+             SELECT * FROM users\n"
+        );
+
+        let read = SnippetStore::from_sql_file("active", Some("reports".to_string()), &content);
+        assert_eq!(read.name, snippet.name);
+        assert_eq!(read.description, snippet.description);
+        assert_eq!(read.tags, snippet.tags);
+        assert_eq!(read.sql, "-- not metadata\nSELECT * FROM users");
+        assert_eq!(read.folder.as_deref(), Some("reports"));
+    }
+
+    #[test]
+    fn reads_sql_files_without_metadata() {
+        let read = SnippetStore::from_sql_file("daily", None, "\nSELECT 1;\n");
+        assert_eq!(read.name, "daily");
+        assert_eq!(read.sql, "SELECT 1;");
+        assert_eq!(read.description, None);
+        assert!(read.tags.is_empty());
+    }
+
+    #[test]
+    fn sanitizes_file_names() {
+        let cases = [
+            ("Daily report", "Daily report"),
+            ("a/b\\c:d*e?f\"g<h>i|j", "a_b_c_d_e_f_g_h_i_j"),
+            ("  ", "snippet"),
+            ("..", "snippet"),
+        ];
+        for (name, expected) in cases {
+            assert_eq!(SnippetStore::file_stem(name), expected, "{:?}", name);
+        }
+    }
+}
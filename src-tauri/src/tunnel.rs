@@ -0,0 +1,248 @@
+use crate::connection::SshConfig;
+use crate::db::{DbError, DbResult};
+use russh::client::{self, Handle};
+use russh::keys::{HashAlg, PrivateKeyWithHashAlg, PublicKey};
+use russh::Disconnect;
+use serde::Serialize;
+#[cfg(unix)]
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::task::JoinHandle;
+
+/// Returned instead of connecting when the SSH server presents a key that is
+/// neither in `known_hosts` nor accepted for this connection. Storing
+/// `fingerprint` as the connection's accepted host key lets the retry through.
+#[derive(Debug, Clone, Serialize)]
+pub struct HostKeyConfirmation {
+    pub host: String,
+    pub port: u16,
+    pub fingerprint: String,
+}
+
+struct TunnelHandler {
+    host: String,
+    port: u16,
+    accepted_fingerprint: Option<String>,
+    /// Fingerprint of a key rejected for being unknown, for the caller to
+    /// show the user.
+    unknown_fingerprint: Arc<Mutex<Option<String>>>,
+}
+
+impl client::Handler for TunnelHandler {
+    type Error = russh::Error;
+
+    async fn check_server_key(
+        &mut self,
+        server_public_key: &PublicKey,
+    ) -> Result<bool, Self::Error> {
+        match russh::keys::check_known_hosts(&self.host, self.port, server_public_key) {
+            Ok(true) => return Ok(true),
+            Err(russh::keys::Error::KeyChanged { .. }) => return Ok(false),
+            _ => {}
+        }
+
+        let fingerprint = server_public_key.fingerprint(HashAlg::Sha256).to_string();
+        if self.accepted_fingerprint.as_ref() == Some(&fingerprint) {
+            return Ok(true);
+        }
+        *self.unknown_fingerprint.lock().unwrap() = Some(fingerprint);
+        Ok(false)
+    }
+}
+
+/// Where the database driver reaches the forwarded port.
+pub enum TunnelEndpoint {
+    /// A Unix socket in a private directory. The driver keeps the database
+    /// host name, which TLS still verifies against.
+    #[cfg(unix)]
+    Socket(PathBuf),
+    /// A loopback TCP port, on platforms without Unix sockets.
+    #[cfg(not(unix))]
+    Tcp(u16),
+}
+
+pub struct SshTunnel {
+    endpoint: TunnelEndpoint,
+    session: Arc<Handle<TunnelHandler>>,
+    listener_task: JoinHandle<()>,
+}
+
+impl SshTunnel {
+    pub async fn open(ssh: &SshConfig, remote_host: &str, remote_port: u16) -> DbResult<Self> {
+        let config = Arc::new(client::Config {
+            keepalive_interval: Some(Duration::from_secs(30)),
+            ..Default::default()
+        });
+        let unknown_fingerprint = Arc::new(Mutex::new(None));
+        let handler = TunnelHandler {
+            host: ssh.host.clone(),
+            port: ssh.port,
+            accepted_fingerprint: ssh.host_key_fingerprint.clone(),
+            unknown_fingerprint: unknown_fingerprint.clone(),
+        };
+
+        let mut session = tokio::time::timeout(
+            Duration::from_secs(10),
+            client::connect(config, (ssh.host.as_str(), ssh.port), handler),
+        )
+        .await
+        .map_err(|_| DbError::Tunnel(format!("Timed out connecting to {}", ssh.host)))?
+        .map_err(|e| match unknown_fingerprint.lock().unwrap().take() {
+            Some(fingerprint) => DbError::UnknownHostKey {
+                host: ssh.host.clone(),
+                port: ssh.port,
+                fingerprint,
+            },
+            None => DbError::Tunnel(e.to_string()),
+        })?;
+
+        let auth = match &ssh.private_key_path {
+            Some(path) => {
+                let passphrase = Some(ssh.passphrase.as_str()).filter(|p| !p.is_empty());
+                let key = russh::keys::load_secret_key(path, passphrase)
+                    .map_err(|e| DbError::Tunnel(format!("Failed to load private key: {}", e)))?;
+                let hash_alg = session
+                    .best_supported_rsa_hash()
+                    .await
+                    .map_err(|e| DbError::Tunnel(e.to_string()))?
+                    .flatten();
+                session
+                    .authenticate_publickey(
+                        &ssh.username,
+                        PrivateKeyWithHashAlg::new(Arc::new(key), hash_alg),
+                    )
+                    .await
+            }
+            None => {
+                session
+                    .authenticate_password(&ssh.username, &ssh.password)
+                    .await
+            }
+        }
+        .map_err(|e| DbError::Tunnel(e.to_string()))?;
+
+        if !auth.success() {
+            return Err(DbError::Tunnel(format!(
+                "SSH authentication failed for {}@{}",
+                ssh.username, ssh.host
+            )));
+        }
+
+        let session = Arc::new(session);
+        let (endpoint, listener_task) =
+            Self::listen(session.clone(), remote_host.to_string(), remote_port).await?;
+
+        Ok(Self {
+            endpoint,
+            session,
+            listener_task,
+        })
+    }
+
+    /// Accepts local connections on a socket only the current user can reach.
+    /// The file is named `.s.PGSQL.<port>` because that is what PostgreSQL
+    /// clients look for in a socket directory.
+    #[cfg(unix)]
+    async fn listen(
+        session: Arc<Handle<TunnelHandler>>,
+        remote_host: String,
+        remote_port: u16,
+    ) -> DbResult<(TunnelEndpoint, JoinHandle<()>)> {
+        use std::os::unix::fs::DirBuilderExt;
+
+        let dir = std::env::temp_dir().join(format!("db_gui-tunnel-{}", uuid::Uuid::new_v4()));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&dir)
+            .map_err(|e| DbError::Tunnel(e.to_string()))?;
+        let path = dir.join(format!(".s.PGSQL.{}", remote_port));
+        let listener =
+            tokio::net::UnixListener::bind(&path).map_err(|e| DbError::Tunnel(e.to_string()))?;
+
+        let listener_task = tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(forward(
+                    session.clone(),
+                    socket,
+                    remote_host.clone(),
+                    remote_port,
+                    ("127.0.0.1".to_string(), 0),
+                ));
+            }
+        });
+        Ok((TunnelEndpoint::Socket(path), listener_task))
+    }
+
+    #[cfg(not(unix))]
+    async fn listen(
+        session: Arc<Handle<TunnelHandler>>,
+        remote_host: String,
+        remote_port: u16,
+    ) -> DbResult<(TunnelEndpoint, JoinHandle<()>)> {
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0))
+            .await
+            .map_err(|e| DbError::Tunnel(e.to_string()))?;
+        let local_port = listener
+            .local_addr()
+            .map_err(|e| DbError::Tunnel(e.to_string()))?
+            .port();
+
+        let listener_task = tokio::spawn(async move {
+            while let Ok((socket, peer)) = listener.accept().await {
+                tokio::spawn(forward(
+                    session.clone(),
+                    socket,
+                    remote_host.clone(),
+                    remote_port,
+                    (peer.ip().to_string(), peer.port()),
+                ));
+            }
+        });
+        Ok((TunnelEndpoint::Tcp(local_port), listener_task))
+    }
+
+    pub fn endpoint(&self) -> &TunnelEndpoint {
+        &self.endpoint
+    }
+
+    pub async fn close(self) {
+        self.listener_task.abort();
+        #[cfg(unix)]
+        {
+            let TunnelEndpoint::Socket(path) = &self.endpoint;
+            if let Some(dir) = path.parent() {
+                let _ = std::fs::remove_dir_all(dir);
+            }
+        }
+        let _ = self
+            .session
+            .disconnect(Disconnect::ByApplication, "", "en")
+            .await;
+    }
+}
+
+async fn forward<S>(
+    session: Arc<Handle<TunnelHandler>>,
+    mut socket: S,
+    remote_host: String,
+    remote_port: u16,
+    (originator_host, originator_port): (String, u16),
+) where
+    S: AsyncRead + AsyncWrite + Unpin,
+{
+    let Ok(channel) = session
+        .channel_open_direct_tcpip(
+            remote_host,
+            remote_port as u32,
+            originator_host,
+            originator_port as u32,
+        )
+        .await
+    else {
+        return;
+    };
+    let mut stream = channel.into_stream();
+    let _ = tokio::io::copy_bidirectional(&mut socket, &mut stream).await;
+}
@@ -5,9 +5,12 @@ import type {
   ConnectionListItem,
   ConnectionInput,
   TestConnectionInput,
+  ImportSource,
+  ImportReport,
   SchemaInfo,
   TableInfo,
   ColumnInfo,
+  VaultStatus,
 } from "../types"
 
 export const useConnectionsStore = defineStore("connections", () => {
@@ -18,6 +21,7 @@ export const useConnectionsStore = defineStore("connections", () => {
   const columns = ref<Map<string, ColumnInfo[]>>(new Map())
   const loading = ref(false)
   const error = ref<string | null>(null)
+  const vault = ref<VaultStatus | null>(null)
 
   const activeConnection = computed(() =>
     connections.value.find((c) => c.id === activeConnectionId.value)
@@ -35,12 +39,38 @@ export const useConnectionsStore = defineStore("connections", () => {
     }
   }
 
+  async function loadVaultStatus() {
+    vault.value = await invoke<VaultStatus>("get_vault_status")
+  }
+
+  async function unlockVault(masterPassword: string) {
+    await invoke("unlock_vault", { masterPassword })
+    await loadVaultStatus()
+  }
+
   async function saveConnection(input: ConnectionInput): Promise<string> {
     const id = await invoke<string>("save_connection", { input })
     await loadConnections()
     return id
   }
 
+  async function updateConnection(id: string, input: ConnectionInput) {
+    await invoke("update_connection", { id, input })
+    await loadConnections()
+  }
+
+  async function duplicateConnection(id: string): Promise<string> {
+    const newId = await invoke<string>("duplicate_connection", { id })
+    await loadConnections()
+    return newId
+  }
+
+  async function importConnections(source: ImportSource): Promise<ImportReport> {
+    const report = await invoke<ImportReport>("import_connections", { source })
+    await loadConnections()
+    return report
+  }
+
   async function deleteConnection(id: string) {
     await invoke("delete_connection", { id })
     if (activeConnectionId.value === id) {
@@ -55,6 +85,11 @@ export const useConnectionsStore = defineStore("connections", () => {
     return await invoke<boolean>("test_connection", { input })
   }
 
+  async function trustHostKey(id: string, fingerprint: string) {
+    await invoke("trust_host_key", { id, fingerprint })
+    await loadConnections()
+  }
+
   const CONNECTION_TIMEOUT_MS = 15000
 
   async function connect(id: string) {
@@ -156,10 +191,17 @@ export const useConnectionsStore = defineStore("connections", () => {
     columns,
     loading,
     error,
+    vault,
+    loadVaultStatus,
+    unlockVault,
     loadConnections,
     saveConnection,
+    updateConnection,
+    duplicateConnection,
+    importConnections,
     deleteConnection,
     testConnection,
+    trustHostKey,
     connect,
     disconnect,
     loadSchemas,
@@ -1,8 +1,9 @@
 import { defineStore } from "pinia"
 import { ref, computed } from "vue"
+import { invoke } from "@tauri-apps/api/core"
 
 export interface HistoryEntry {
-  id: string
+  id: number
   sql: string
   connectionId: string
   timestamp: number
@@ -12,72 +13,79 @@ export interface HistoryEntry {
   error?: string
 }
 
-const STORAGE_KEY = "db_gui_query_history"
 const MAX_ENTRIES = 100
 
-function generateId(): string {
-  return Math.random().toString(36).substring(2, 9)
+interface BackendHistoryEntry {
+  id: number
+  connection_id: string
+  sql: string
+  executed_at: number
+  execution_time_ms: number
+  row_count: number | null
+  error: string | null
+  status: "success" | "error"
+}
+
+export interface HistoryFilter {
+  connectionId?: string
+  search?: string
+  status?: "success" | "error"
+  since?: number
+  until?: number
+  limit?: number
+  offset?: number
+}
+
+function fromBackend(entry: BackendHistoryEntry): HistoryEntry {
+  return {
+    id: entry.id,
+    sql: entry.sql,
+    connectionId: entry.connection_id,
+    timestamp: entry.executed_at,
+    status: entry.status,
+    duration: entry.execution_time_ms,
+    rowsAffected: entry.row_count ?? undefined,
+    error: entry.error ?? undefined,
+  }
 }
 
 export const useHistoryStore = defineStore("history", () => {
   const entries = ref<HistoryEntry[]>([])
 
-  function loadFromStorage() {
-    try {
-      const stored = localStorage.getItem(STORAGE_KEY)
-      if (stored) {
-        entries.value = JSON.parse(stored)
-      }
-    } catch (e) {
-      console.error("Failed to load history:", e)
-      entries.value = []
-    }
+  async function search(filter: HistoryFilter = {}): Promise<HistoryEntry[]> {
+    const result = await invoke<BackendHistoryEntry[]>("search_query_history", {
+      filter: {
+        connection_id: filter.connectionId,
+        search: filter.search,
+        status: filter.status,
+        since: filter.since,
+        until: filter.until,
+        limit: filter.limit,
+        offset: filter.offset,
+      },
+    })
+    return result.map(fromBackend)
   }
 
-  function saveToStorage() {
+  // Queries are recorded by the backend as they run; this only reloads.
+  async function refresh() {
     try {
-      localStorage.setItem(STORAGE_KEY, JSON.stringify(entries.value))
+      entries.value = await search({ limit: MAX_ENTRIES })
     } catch (e) {
-      console.error("Failed to save history:", e)
-    }
-  }
-
-  function addEntry(
-    sql: string,
-    connectionId: string,
-    status: "success" | "error",
-    duration: number,
-    rowsAffected?: number,
-    error?: string
-  ) {
-    const entry: HistoryEntry = {
-      id: generateId(),
-      sql: sql.trim(),
-      connectionId,
-      timestamp: Date.now(),
-      status,
-      duration,
-      rowsAffected,
-      error,
-    }
-
-    entries.value.unshift(entry)
-
-    if (entries.value.length > MAX_ENTRIES) {
-      entries.value = entries.value.slice(0, MAX_ENTRIES)
+      console.error("Failed to load history:", e)
     }
-
-    saveToStorage()
   }
 
-  function clearHistory() {
-    entries.value = []
-    saveToStorage()
+  async function clearHistory(connectionId?: string) {
+    await invoke<number>("prune_query_history", {
+      options: { connection_id: connectionId },
+    })
+    await refresh()
   }
 
-  function removeEntry(id: string) {
+  async function removeEntry(id: number) {
+    await invoke("delete_history_entry", { id })
     entries.value = entries.value.filter((e) => e.id !== id)
-    saveToStorage()
   }
 
   function getEntriesForConnection(connectionId: string): HistoryEntry[] {
@@ -94,14 +102,15 @@ export const useHistoryStore = defineStore("history", () => {
     () => entries.value.filter((e) => e.status === "error").length
   )
 
-  loadFromStorage()
+  refresh()
 
   return {
     entries,
     recentEntries,
     successCount,
     errorCount,
-    addEntry,
+    search,
+    refresh,
     clearHistory,
     removeEntry,
     getEntriesForConnection,
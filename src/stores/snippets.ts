@@ -0,0 +1,88 @@
+import { defineStore } from "pinia"
+import { ref, computed } from "vue"
+import { invoke } from "@tauri-apps/api/core"
+import type { QueryParams, QueryResult, Snippet, SnippetInput } from "../types"
+
+const PLACEHOLDER = /\{\{\s*([^}]*?)\s*\}\}/g
+
+export const useSnippetsStore = defineStore("snippets", () => {
+  const snippets = ref<Snippet[]>([])
+
+  const folders = computed(() =>
+    [...new Set(snippets.value.map((s) => s.folder).filter((f): f is string => !!f))].sort()
+  )
+
+  async function load(connectionId?: string) {
+    try {
+      snippets.value = await invoke<Snippet[]>("get_snippets", { connectionId })
+    } catch (e) {
+      console.error("Failed to load snippets:", e)
+    }
+  }
+
+  async function saveSnippet(input: SnippetInput): Promise<string> {
+    const id = await invoke<string>("save_snippet", { input })
+    await load()
+    return id
+  }
+
+  async function updateSnippet(id: string, input: SnippetInput) {
+    await invoke("update_snippet", { id, input })
+    await load()
+  }
+
+  async function deleteSnippet(id: string) {
+    await invoke("delete_snippet", { id })
+    snippets.value = snippets.value.filter((s) => s.id !== id)
+  }
+
+  async function importSnippets(path: string): Promise<string[]> {
+    const ids = await invoke<string[]>("import_snippets", { path })
+    await load()
+    return ids
+  }
+
+  async function exportSnippets(path: string, format: "json" | "sql", ids?: string[]) {
+    return invoke<number>("export_snippets", { path, format, ids })
+  }
+
+  function placeholders(snippet: Snippet): string[] {
+    return [...new Set([...snippet.sql.matchAll(PLACEHOLDER)].map((m) => m[1]))]
+  }
+
+  async function executeSnippet(
+    connectionId: string,
+    snippetId: string,
+    variables: Record<string, string>,
+    options: { limit?: number; params?: QueryParams; queryId?: string } = {}
+  ): Promise<QueryResult> {
+    return invoke<QueryResult>("execute_snippet", {
+      connectionId,
+      snippetId,
+      variables,
+      limit: options.limit,
+      params: options.params,
+      queryId: options.queryId,
+    })
+  }
+
+  function getSnippetsForConnection(connectionId: string): Snippet[] {
+    return snippets.value.filter((s) => !s.connection_id || s.connection_id === connectionId)
+  }
+
+  load()
+
+  return {
+    snippets,
+    folders,
+    load,
+    saveSnippet,
+    updateSnippet,
+    deleteSnippet,
+    importSnippets,
+    exportSnippets,
+    placeholders,
+    executeSnippet,
+    getSnippetsForConnection,
+  }
+})
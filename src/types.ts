@@ -1,5 +1,24 @@
 export type DatabaseType = "postgresql" | "mysql" | "sqlite"
 
+export interface SshConfig {
+  host: string
+  port: number
+  username: string
+  password?: string
+  private_key_path: string | null
+  passphrase?: string
+  host_key_fingerprint?: string | null
+}
+
+export type SslMode = "disable" | "prefer" | "require" | "verify-ca" | "verify-full"
+
+export interface TlsConfig {
+  ssl_mode: SslMode
+  ca_cert_path: string | null
+  client_cert_path: string | null
+  client_key_path: string | null
+}
+
 export interface ConnectionListItem {
   id: string
   name: string
@@ -8,6 +27,13 @@ export interface ConnectionListItem {
   port: number
   database: string
   username: string
+  ssh: SshConfig | null
+  tls: TlsConfig
+  parameters: Record<string, string>
+  read_only: boolean
+  statement_timeout_ms: number | null
+  max_rows: number | null
+  production: boolean
 }
 
 export interface ConnectionInput {
@@ -18,6 +44,33 @@ export interface ConnectionInput {
   database: string
   username: string
   password: string
+  ssh?: SshConfig | null
+  tls?: TlsConfig
+  parameters?: Record<string, string>
+  read_only?: boolean
+  statement_timeout_ms?: number | null
+  max_rows?: number | null
+  production?: boolean
+  /** Stored secrets to delete on update; an empty password keeps the stored one. */
+  clear_secrets?: SecretKind[]
+}
+
+export type SecretKind = "password" | "ssh_password" | "ssh_passphrase"
+
+export interface VaultStatus {
+  initialized: boolean
+  unlocked: boolean
+}
+
+export type ImportSource =
+  | { kind: "uri"; uri: string }
+  | { kind: "pgpass"; path?: string | null }
+  | { kind: "pg_service"; path?: string | null }
+  | { kind: "my_cnf"; path?: string | null }
+
+export interface ImportReport {
+  imported: { id: string; name: string }[]
+  skipped: { entry: string; reason: string }[]
 }
 
 export interface TestConnectionInput {
@@ -27,6 +80,10 @@ export interface TestConnectionInput {
   database: string
   username: string
   password: string
+  ssh?: SshConfig | null
+  tls?: TlsConfig
+  parameters?: Record<string, string>
+  read_only?: boolean
 }
 
 export interface SchemaInfo {
@@ -44,6 +101,8 @@ export interface ColumnInfo {
   data_type: string
   is_nullable: boolean
   is_primary_key: boolean
+  /** Position within the primary key, starting at 1. */
+  primary_key_ordinal: number | null
   default_value: string | null
 }
 
@@ -60,11 +119,133 @@ export interface TableData {
   total_count: number
 }
 
+export interface ResultColumn {
+  name: string
+  type_name: string
+  nullable: boolean | null
+  source_schema: string | null
+  source_table: string | null
+  source_column: string | null
+}
+
 export interface QueryResult {
-  columns: string[]
+  columns: ResultColumn[]
   rows: unknown[][]
   rows_affected: number
   execution_time_ms: number
+  truncated: boolean
+  messages: QueryMessage[]
+}
+
+export type MessageSeverity = "error" | "warning" | "notice" | "info" | "debug"
+
+export interface QueryMessage {
+  severity: MessageSeverity
+  code: string | null
+  text: string
+}
+
+export interface DryRunResult {
+  rows_affected: number
+  columns: ResultColumn[]
+  sample_rows: unknown[][]
+  execution_time_ms: number
+  messages: QueryMessage[]
+}
+
+export type DestructiveKind =
+  | "unfiltered_update"
+  | "unfiltered_delete"
+  | "truncate"
+  | "drop"
+  | "drop_column"
+
+export interface TableRef {
+  schema: string | null
+  name: string
+}
+
+export interface ConfirmationRequired {
+  kind: DestructiveKind
+  statement: string
+  tables: TableRef[]
+  estimated_rows: number | null
+  confirmation_token: string
+}
+
+export function isConfirmationRequired(e: unknown): e is ConfirmationRequired {
+  return typeof e === "object" && e !== null && "confirmation_token" in e
+}
+
+export interface HostKeyConfirmation {
+  host: string
+  port: number
+  fingerprint: string
+}
+
+export function isHostKeyConfirmation(e: unknown): e is HostKeyConfirmation {
+  return typeof e === "object" && e !== null && "fingerprint" in e
+}
+
+export interface PlanBuffers {
+  shared_hit: number
+  shared_read: number
+  shared_dirtied: number
+  shared_written: number
+  temp_read: number
+  temp_written: number
+}
+
+export interface PlanNode {
+  node_type: string
+  relation: string | null
+  detail: string | null
+  estimated_rows: number | null
+  actual_rows: number | null
+  startup_cost: number | null
+  total_cost: number | null
+  actual_time_ms: number | null
+  loops: number | null
+  buffers: PlanBuffers | null
+  children: PlanNode[]
+}
+
+export interface QueryPlan {
+  nodes: PlanNode[]
+  analyzed: boolean
+  planning_time_ms: number | null
+  execution_time_ms: number | null
+  raw: string
+}
+
+export type ParamValue =
+  | { type: "null" }
+  | { type: "bool"; value: boolean }
+  | { type: "int"; value: number }
+  | { type: "float"; value: number }
+  | { type: "text"; value: string }
+  | { type: "json"; value: unknown }
+
+export type QueryParams = ParamValue[] | Record<string, ParamValue>
+
+export interface Snippet {
+  id: string
+  name: string
+  sql: string
+  description: string | null
+  tags: string[]
+  folder: string | null
+  connection_id: string | null
+  updated_at: number
+}
+
+export interface SnippetInput {
+  name: string
+  sql: string
+  description?: string | null
+  tags?: string[]
+  folder?: string | null
+  connection_id?: string | null
 }
 
 export interface SortColumn {
@@ -72,34 +253,51 @@ export interface SortColumn {
   direction: "asc" | "desc"
 }
 
+export type FilterNode = FilterGroup | FilterCondition
+
+export interface FilterGroup {
+  combinator: "and" | "or"
+  negated?: boolean
+  children: FilterNode[]
+}
+
 export interface FilterCondition {
   column: string
   operator: FilterOperator
   value: string
+  /** Operands of `in`, `notin` and `between`. For `withinlast`, `withinnext`
+   * and `olderthan`, `value` is a span such as "7 days". */
+  values?: string[]
 }
 
 export type FilterOperator =
   | "equals"
   | "notequals"
   | "contains"
+  | "notcontains"
   | "startswith"
   | "endswith"
   | "greaterthan"
+  | "greaterthanorequal"
   | "lessthan"
+  | "lessthanorequal"
+  | "like"
+  | "notlike"
+  | "in"
+  | "notin"
+  | "between"
+  | "regex"
   | "isnull"
   | "isnotnull"
-  | "raw"
-
-export interface RawWhereClause {
-  column: string
-  fragment: string
-}
+  | "isempty"
+  | "withinlast"
+  | "withinnext"
+  | "olderthan"
 
 export interface RowUpdate {
   schema: string
   table: string
-  primary_key_column: string
-  primary_key_value: unknown
+  primary_key: Record<string, unknown>
   updates: Record<string, unknown>
 }
 
@@ -112,8 +310,7 @@ export interface RowInsert {
 export interface RowDelete {
   schema: string
   table: string
-  primary_key_column: string
-  primary_key_value: unknown
+  primary_key: Record<string, unknown>
 }
 
 export type TabType =